@@ -26,7 +26,7 @@ zip_root = PurePath(f'{manifest["name"]}')
 
 filename = f'{manifest["name"]}-{manifest["version"]}.zip'
 
-result = subprocess.run([wasm_pack, "build", "--target", "web", "--out-dir", build_dir, root_dir / rust_dir])
+result = subprocess.run([wasm_pack, "build", "--target", "web", "--out-dir", build_dir, root_dir / rust_dir, "--", "--features", "wasm"])
 if result.returncode != 0:
    raise Exception("Wasm build failed")
 
@@ -7,9 +7,9 @@ init().then(() => {
 	WallsLayer.prototype.computePolygon = wasmComputePolygon;
 	Hooks.on("canvasInit", wipeCache);
 	Hooks.on("canvasReady", wipeCache);
-	Hooks.on("createWall", wipeCache);
-	Hooks.on("updateWall", wipeCache);
-	Hooks.on("deleteWall", wipeCache);
+	Hooks.on("createWall", createWall);
+	Hooks.on("updateWall", updateWall);
+	Hooks.on("deleteWall", removeWallFromCache);
 	Hooks.on("createTile", wipeCache);
 	Hooks.on("updateTile", wipeCache);
 	Hooks.on("deleteTile", wipeCache);
@@ -17,10 +17,20 @@ init().then(() => {
 	window.lichtgeschwindigkeit = {
 		build_scene,
 		generate_test,
+		minimize_test,
+		capture_scene,
 	}
 });
 
 Hooks.once("init", () => {
+	game.settings.register("lichtgeschwindigkeit", "fallback", {
+		name: "Fall back to sampled vision on errors",
+		hint: "If the vision of a token or light can't be calculated due to a bug, approximate it by casting rays instead of failing. The data needed for a bug report is still written to the console.",
+		scope: "client",
+		config: true,
+		type: Boolean,
+		default: false,
+	});
 	// This can affect the outcome of vision calculations, so we wipe the cache just to be sure
 	wallHeightEnabled = game.modules.get("wall-height")?.active;
 	wipeCache();
@@ -35,6 +45,82 @@ function wipeCache() {
 	cache = undefined;
 }
 
+// The flag is part of the scene document, which every client downloads together with the scene. A stored cache takes
+// about 25 bytes per wall, so only scenes with up to roughly 40000 walls store it.
+const MAX_STORED_CACHE_LENGTH = 1000000;
+
+// Building the cache of a large scene takes a while, so the GM stores it in a scene flag. It is only used while the
+// geometry of the walls of the scene is the same as the one it has been built of.
+function loadOrBuildCache() {
+	const walls = canvas.walls.placeables;
+	const stored = canvas.scene.getFlag("lichtgeschwindigkeit", "cache");
+	if (stored) {
+		const loaded = Lichtgeschwindigkeit.loadCache(stored, walls, wallHeightEnabled);
+		if (loaded)
+			return loaded;
+	}
+	const built = Lichtgeschwindigkeit.buildCache(walls, wallHeightEnabled);
+	storeCache(built);
+	return built;
+}
+
+function storeCache(cache) {
+	if (!cache || !game.user.isGM)
+		return;
+	const serialized = Lichtgeschwindigkeit.serializeCache(cache);
+	if (serialized.length <= MAX_STORED_CACHE_LENGTH)
+		canvas.scene.setFlag("lichtgeschwindigkeit", "cache", serialized);
+	else if (canvas.scene.getFlag("lichtgeschwindigkeit", "cache"))
+		canvas.scene.unsetFlag("lichtgeschwindigkeit", "cache");
+}
+
+// Walls are often edited in quick succession, so the stored cache is only replaced once the editing has paused.
+const storeCacheSoon = debounce(() => storeCache(cache), 5000);
+
+function getWallId(...args) {
+	// Foundry 0.7 passes (scene, data, ...) to wall hooks, Foundry 0.8 passes (document, ...)
+	if (args[0] instanceof Scene)
+		return args[1]._id;
+	return args[0].id;
+}
+
+function updateWallInCache(update, ...args) {
+	if (!cache)
+		return;
+	const wall = canvas.walls.get(getWallId(...args));
+	if (!wall) {
+		// The wall isn't part of the scene that is currently being viewed
+		return;
+	}
+	update(cache, wall, wallHeightEnabled);
+}
+
+function createWall(...args) {
+	updateWallInCache(Lichtgeschwindigkeit.insertWall, ...args);
+	storeCacheSoon();
+}
+
+function updateWall(...args) {
+	// Foundry 0.7 passes (scene, data, changes, ...), Foundry 0.8 passes (document, changes, ...)
+	const changes = args[0] instanceof Scene ? args[2] : args[1];
+	const changedKeys = Object.keys(changes).filter(key => key !== "_id");
+	if (cache && changedKeys.length === 1 && changedKeys[0] === "ds") {
+		Lichtgeschwindigkeit.updateDoorState(cache, getWallId(...args), changes.ds);
+		return;
+	}
+	updateWallInCache(Lichtgeschwindigkeit.replaceWall, ...args);
+	// Only the geometry and the heights of the walls are part of the stored cache
+	if ("c" in changes || "flags" in changes)
+		storeCacheSoon();
+}
+
+function removeWallFromCache(...args) {
+	if (!cache)
+		return;
+	Lichtgeschwindigkeit.removeWall(cache, getWallId(...args));
+	storeCacheSoon();
+}
+
 function hookUpdateOcclusion() {
 	let original = Tile.prototype.updateOcclusion;
 	Tile.prototype.updateOcclusion = function(tokens) {
@@ -46,18 +132,27 @@ function hookUpdateOcclusion() {
 	}
 }
 
-function wasmComputePolygon(origin, radius, { type = "sight", angle = 360, density = 6, rotation = 0, unrestricted = false } = {}) {
+// The parameters of a polygon computation as expected by `samplePolygon` and `serializeScene`
+function polygonSource(origin, radius, { type = "sight", angle = 360, density = 6, rotation = 0 } = {}) {
 	// TODO This hotfix may no longer be necessary in foundry 9
 	if (type === "sight")
 		radius = Math.max(radius, canvas.dimensions.size >> 1); // canvas.dimensions.size >> 1 is a fast method of calculating canvas.dimensions.size / 2
 
-	let debugEnabled = CONFIG.debug.sightRays;
 	// The maximum ray distance needs to reach all areas of the canvas
 	let d = canvas.dimensions;
 	const dx = Math.max(origin.x, d.width - origin.x);
 	const dy = Math.max(origin.y, d.height - origin.y);
 	const distance = Math.max(radius, Math.hypot(dx, dy));
 	const height = game.currentTokenElevation ?? 0;
+	return {origin: {x: origin.x, y: origin.y}, height, radius, distance, density, angle, rotation, type};
+}
+
+function wasmComputePolygon(origin, radius, { type = "sight", angle = 360, density = 6, rotation = 0, unrestricted = false } = {}) {
+	const source = polygonSource(origin, radius, {type, angle, density, rotation});
+	const {height, distance} = source;
+	radius = source.radius;
+
+	let debugEnabled = CONFIG.debug.sightRays;
 
 	let internals = null;
 	if (debugEnabled)
@@ -71,7 +166,7 @@ function wasmComputePolygon(origin, radius, { type = "sight", angle = 360, densi
 	}
 	else {
 		if (!cache)
-			cache = Lichtgeschwindigkeit.buildCache(canvas.walls.placeables, wallHeightEnabled);
+			cache = loadOrBuildCache();
 		cacheRef = cache
 	}
 
@@ -84,23 +179,31 @@ function wasmComputePolygon(origin, radius, { type = "sight", angle = 360, densi
 
 	let sight;
 	try {
-		sight = Lichtgeschwindigkeit.computePolygon(cacheRef, origin, height, radius, distance, density, angle, rotation, type, internals);
+		sight = Lichtgeschwindigkeit.computePolygon(cacheRef, origin, height, radius, distance, density, angle, rotation, type, internals, true);
 	}
 	catch (e) {
 		console.error(e);
 		console.error("Data to reproduce the error (please always include this in bug reports!):");
 		logParams(true, console.error);
-		throw e;
+		if (!game.settings.get("lichtgeschwindigkeit", "fallback"))
+			throw e;
+		sight = Lichtgeschwindigkeit.samplePolygon(cacheRef, source, true);
 	}
 
 	// Lichtgeschwindigkeit improves the speed of PIXI.Polygon.contains.
 	// Those improvements outperform the improvements done by SourcePolygon.
 	// As a result we don't construct SourcePolygon here.
-	const los = new PIXI.Polygon(...sight.los);
-	const fov = new PIXI.Polygon(...sight.fov);
+	const los = new PIXI.Polygon(Array.from(sight.los));
+	const fov = new PIXI.Polygon(Array.from(sight.fov));
 
 	if (debugEnabled) {
-		_visualizeSight(internals.endpoints, origin, radius, distance, los, fov, sight.los, true);
+		const defects = Lichtgeschwindigkeit.validatePolygons(source, sight.los, sight.fov);
+		if (defects.length > 0) {
+			console.error("Lichtgeschwindigkeit | The computed polygons are invalid:", defects);
+			console.error("Data to reproduce the error (please always include this in bug reports!):");
+			logParams(true, console.error);
+		}
+		_visualizeSight(internals.endpoints, origin, radius, distance, los, fov, los.points, true);
 	}
 
 	return { rays: null, los, fov };
@@ -116,6 +219,24 @@ function rustifyParams(cache, origin, height, radius, distance, density, angle,
 	error_fn(Lichtgeschwindigkeit.serializeData(cache, origin, height, radius, distance, density, angle, rotation, type));
 }
 
+function capture_scene() {
+	if (!cache) {
+		console.warn("Lichtgeschwindigkeit | No vision has been computed for this scene yet");
+		return;
+	}
+	// Universal lights ignore walls, so they don't use the cache
+	const sources = [];
+	for (const [layer, type] of [[canvas.sight, "sight"], [canvas.lighting, "light"]]) {
+		for (const source of layer.sources.values()) {
+			if ((source.data?.type ?? source.type) === CONST.SOURCE_TYPES.UNIVERSAL)
+				continue;
+			const {angle, rotation} = source;
+			sources.push(polygonSource(source, source.radius, {type: source.sourceType ?? type, angle, rotation}));
+		}
+	}
+	console.warn(Lichtgeschwindigkeit.serializeScene(cache, sources));
+}
+
 function _visualizeSight(endpoints, origin, radius, distance, los, fov, tangentPoints, clear = true) {
 	/*if (canvas.tokens.controlled.length === 0)
 		return;
@@ -140,8 +261,8 @@ function _visualizeSight(endpoints, origin, radius, distance, los, fov, tangentP
 		debug.lineStyle(0).beginFill(color, 1.0).drawCircle(endpoint.x, endpoint.y, 9).endFill();
 	}
 
-	for (const point of tangentPoints) {
-		debug.lineStyle(2, 0xDDFF00).drawCircle(point.x, point.y, 5);
+	for (let i = 0; i < tangentPoints.length; i += 2) {
+		debug.lineStyle(2, 0xDDFF00).drawCircle(tangentPoints[i], tangentPoints[i + 1], 5);
 	}
 
 	// Walls
@@ -166,7 +287,13 @@ function build_scene() {
 				icon: '<i class="fas fa-check"></i>',
 				callback: html => {
 					let data = document.getElementById("lichtgeschwindigkeit-debug-input").value;
-					data = Lichtgeschwindigkeit.deserializeData(data);
+					try {
+						data = Lichtgeschwindigkeit.deserializeData(data);
+					}
+					catch (e) {
+						ui.notifications.error(e.message);
+						return;
+					}
 					import("./scene_builder.js").then((module) => module.build_scene(data));
 				}
 			}
@@ -174,6 +301,32 @@ function build_scene() {
 	}).render(true);
 }
 
+// The first line is the output of `serializeData`. If a second line contains a known-good test of the same call,
+// any other result is treated as a failure, otherwise only errors are. Calls that panic can't be minimized in the browser.
+function minimize_test() {
+	new Dialog({
+		content: "<textarea id='lichtgeschwindigkeit-debug-input'></textarea>",
+		buttons: {
+			ok: {
+				icon: '<i class="fas fa-check"></i>',
+				callback: html => {
+					const [data, expected] = document.getElementById("lichtgeschwindigkeit-debug-input").value.trim().split(/\s*\n\s*/);
+					try {
+						const reproducer = Lichtgeschwindigkeit.minimizeData(data, expected);
+						if (reproducer === undefined)
+							ui.notifications.warn("The call doesn't fail");
+						else
+							console.warn(reproducer);
+					}
+					catch (e) {
+						ui.notifications.error(e.message);
+					}
+				}
+			}
+		}
+	}).render(true);
+}
+
 function generate_test() {
 	new Dialog({
 		content: "<textarea id='lichtgeschwindigkeit-debug-input'></textarea>",
@@ -182,7 +335,12 @@ function generate_test() {
 				icon: '<i class="fas fa-check"></i>',
 				callback: html => {
 					let data = document.getElementById("lichtgeschwindigkeit-debug-input").value;
-					console.warn(Lichtgeschwindigkeit.generateTest(data));
+					try {
+						console.warn(Lichtgeschwindigkeit.generateTest(data));
+					}
+					catch (e) {
+						ui.notifications.error(e.message);
+					}
 				}
 			}
 		}
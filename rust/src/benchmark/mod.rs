@@ -0,0 +1,82 @@
+mod scenes;
+
+pub use scenes::{synthetic_scenes, Rng};
+
+use crate::raycasting::{
+	compute_polygon, Cache, Internals, PhaseTimings, RaycastingError, VisionAngle,
+};
+use crate::serialization::SceneSnapshot;
+use std::time::{Duration, Instant};
+
+/// The median timings of a scene over all iterations
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Measurement {
+	/// `Cache::build`
+	pub build: Duration,
+	/// The phases of `compute_polygon`, summed up over all sources of the scene
+	pub phases: PhaseTimings,
+}
+
+impl Measurement {
+	/// The time of the whole computation, from building the cache to the last polygon
+	pub fn total(&self) -> Duration {
+		self.build
+			+ self.phases.prepare_data
+			+ self.phases.calculate_los
+			+ self.phases.calculate_fov
+			+ self.phases.fill_gaps
+	}
+}
+
+/// Builds the cache of the scene and computes the polygons of all its sources `iterations` times.
+/// The timings are only meaningful in release builds.
+pub fn measure(scene: &SceneSnapshot, iterations: usize) -> Result<Measurement, RaycastingError> {
+	let mut measurements = Vec::with_capacity(iterations);
+	for _ in 0..iterations {
+		let start = Instant::now();
+		let cache = scene.build_cache();
+		let build = start.elapsed();
+		measurements.push(Measurement {
+			build,
+			phases: compute_phases(&cache, scene)?,
+		});
+	}
+	let median = |duration: fn(&Measurement) -> Duration| {
+		let mut durations = measurements.iter().map(duration).collect::<Vec<_>>();
+		durations.sort_unstable();
+		durations
+			.get(durations.len() / 2)
+			.copied()
+			.unwrap_or_default()
+	};
+	Ok(Measurement {
+		build: median(|measurement| measurement.build),
+		phases: PhaseTimings {
+			prepare_data: median(|measurement| measurement.phases.prepare_data),
+			calculate_los: median(|measurement| measurement.phases.calculate_los),
+			calculate_fov: median(|measurement| measurement.phases.calculate_fov),
+			fill_gaps: median(|measurement| measurement.phases.fill_gaps),
+		},
+	})
+}
+
+fn compute_phases(cache: &Cache, scene: &SceneSnapshot) -> Result<PhaseTimings, RaycastingError> {
+	let mut internals = Internals {
+		timings: Some(PhaseTimings::default()),
+		..Internals::default()
+	};
+	for source in &scene.sources {
+		compute_polygon(
+			cache,
+			source.origin,
+			source.height,
+			source.radius,
+			source.distance,
+			source.density,
+			VisionAngle::from_rotation_and_angle(source.rotation, source.angle, source.origin),
+			source.polygon_type,
+			Some(&mut internals),
+		)?;
+	}
+	Ok(internals.timings.unwrap())
+}
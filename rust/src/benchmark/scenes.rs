@@ -0,0 +1,272 @@
+use crate::geometry::Point;
+use crate::raycasting::{
+	DoorState, DoorType, PolygonSource, PolygonType, WallBase, WallDirection, WallHeight,
+	WallSenseType,
+};
+use crate::serialization::SceneSnapshot;
+use std::f64::consts::PI;
+
+/// A small xorshift generator, so scenes can be reproduced from their seed
+pub struct Rng(u64);
+
+impl Rng {
+	pub fn new(seed: u64) -> Self {
+		// A zero state would only ever produce zeros
+		Self(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	/// A number in [0, 1)
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	pub fn range(&mut self, min: f64, max: f64) -> f64 {
+		min + self.next_f64() * (max - min)
+	}
+
+	pub fn below(&mut self, max: usize) -> usize {
+		(self.next_u64() % max as u64) as usize
+	}
+
+	pub fn chance(&mut self, probability: f64) -> bool {
+		self.next_f64() < probability
+	}
+}
+
+/// Large scenes that resemble common kinds of maps, so changes to the performance can be measured on more than the
+/// small test cases. The scenes are generated from fixed seeds and are the same on every run.
+pub fn synthetic_scenes() -> Vec<(&'static str, SceneSnapshot)> {
+	vec![
+		("forest", forest(&mut Rng::new(1))),
+		("dungeon", dungeon(&mut Rng::new(2))),
+		("cave", cave(&mut Rng::new(3))),
+	]
+}
+
+/// The size of the square the synthetic scenes are enclosed in
+const SIZE: f64 = 6000.0;
+
+/// Radius and vision angle of the sources. A radius of `None` means that the vision is only bounded by walls.
+const VARIATIONS: [(Option<f64>, f64); 5] = [
+	(Some(300.0), 360.0),
+	(Some(1500.0), 360.0),
+	(None, 360.0),
+	(Some(1500.0), 90.0),
+	(None, 60.0),
+];
+
+/// Many small, overlapping trees with foliage that limits vision
+fn forest(rng: &mut Rng) -> SceneSnapshot {
+	let mut walls = boundary();
+	for _ in 0..500 {
+		let center = Point::new(
+			rng.range(200.0, SIZE - 200.0),
+			rng.range(200.0, SIZE - 200.0),
+		);
+		let sides = 6 + rng.below(5);
+		let sense = if rng.chance(0.3) {
+			WallSenseType::LIMITED
+		} else {
+			WallSenseType::NORMAL
+		};
+		let radii = (0..sides)
+			.map(|_| rng.range(20.0, 70.0))
+			.collect::<Vec<_>>();
+		walls.extend(closed_shape(center, &radii, sense));
+	}
+	scene(walls, rng)
+}
+
+/// A grid of rooms that are connected by doors and openings, some of them with pillars
+fn dungeon(rng: &mut Rng) -> SceneSnapshot {
+	const ROOMS: usize = 10;
+	const ROOM_SIZE: f64 = SIZE / ROOMS as f64;
+	const DOOR_WIDTH: f64 = 100.0;
+	let mut walls = boundary();
+	for row in 0..ROOMS {
+		for column in 0..ROOMS {
+			let corner = Point::new(column as f64 * ROOM_SIZE, row as f64 * ROOM_SIZE);
+			// The right and the bottom side of every room that isn't on the boundary
+			let mut sides = vec![];
+			if column + 1 < ROOMS {
+				sides.push((
+					Point::new(corner.x + ROOM_SIZE, corner.y),
+					Point::new(corner.x + ROOM_SIZE, corner.y + ROOM_SIZE),
+				));
+			}
+			if row + 1 < ROOMS {
+				sides.push((
+					Point::new(corner.x, corner.y + ROOM_SIZE),
+					Point::new(corner.x + ROOM_SIZE, corner.y + ROOM_SIZE),
+				));
+			}
+			for (p1, p2) in sides {
+				let kind = rng.next_f64();
+				if kind < 0.25 {
+					// An opening
+					continue;
+				}
+				if kind < 0.6 {
+					walls.push(wall(p1, p2, WallSenseType::NORMAL));
+					continue;
+				}
+				let middle = Point::new((p1.x + p2.x) / 2.0, (p1.y + p2.y) / 2.0);
+				let offset = Point::new(
+					(p2.x - p1.x) / ROOM_SIZE * DOOR_WIDTH / 2.0,
+					(p2.y - p1.y) / ROOM_SIZE * DOOR_WIDTH / 2.0,
+				);
+				let door_start = Point::new(middle.x - offset.x, middle.y - offset.y);
+				let door_end = Point::new(middle.x + offset.x, middle.y + offset.y);
+				walls.push(wall(p1, door_start, WallSenseType::NORMAL));
+				walls.push(wall(door_end, p2, WallSenseType::NORMAL));
+				let mut door = wall(door_start, door_end, WallSenseType::NORMAL);
+				door.door = DoorType::DOOR;
+				door.ds = [DoorState::CLOSED, DoorState::OPEN][rng.below(2)];
+				walls.push(door);
+			}
+			if rng.chance(0.3) {
+				let size = rng.range(40.0, 80.0);
+				let center = Point::new(
+					corner.x + rng.range(size + 50.0, ROOM_SIZE - size - 50.0),
+					corner.y + rng.range(size + 50.0, ROOM_SIZE - size - 50.0),
+				);
+				walls.extend(closed_shape(center, &[size; 4], WallSenseType::NORMAL));
+			}
+		}
+	}
+	scene(walls, rng)
+}
+
+/// An irregular cave made of many short walls, with rocks inside of it
+fn cave(rng: &mut Rng) -> SceneSnapshot {
+	let center = Point::new(SIZE / 2.0, SIZE / 2.0);
+	let mut walls = closed_shape(
+		center,
+		&noisy_radii(rng, 720, 2600.0, 300.0),
+		WallSenseType::NORMAL,
+	);
+	for _ in 0..60 {
+		let angle = rng.range(0.0, 2.0 * PI);
+		let distance = rng.range(300.0, 2000.0);
+		let rock = Point::new(
+			center.x + distance * angle.cos(),
+			center.y + distance * angle.sin(),
+		);
+		let size = rng.range(40.0, 200.0);
+		let vertices = 12 + rng.below(19);
+		walls.extend(closed_shape(
+			rock,
+			&noisy_radii(rng, vertices, size, size / 3.0),
+			WallSenseType::NORMAL,
+		));
+	}
+	scene(walls, rng)
+}
+
+// Radii that change smoothly, so the shape looks organic instead of spiky
+fn noisy_radii(rng: &mut Rng, count: usize, radius: f64, amplitude: f64) -> Vec<f64> {
+	let waves = (1..=4)
+		.map(|frequency| (frequency as f64 * 2.0 + 1.0, rng.range(0.0, 2.0 * PI)))
+		.collect::<Vec<_>>();
+	(0..count)
+		.map(|i| {
+			let angle = i as f64 / count as f64 * 2.0 * PI;
+			let noise = waves
+				.iter()
+				.map(|(frequency, phase)| (angle * frequency + phase).sin() / frequency)
+				.sum::<f64>();
+			radius + amplitude * noise
+		})
+		.collect()
+}
+
+// A polygon around `center` with a vertex at each of the radii, which are evenly spread around the center
+fn closed_shape(center: Point, radii: &[f64], sense: WallSenseType) -> Vec<WallBase> {
+	let vertices = radii
+		.iter()
+		.enumerate()
+		.map(|(i, radius)| {
+			let angle = i as f64 / radii.len() as f64 * 2.0 * PI;
+			Point::new(
+				center.x + radius * angle.cos(),
+				center.y + radius * angle.sin(),
+			)
+		})
+		.collect::<Vec<_>>();
+	(0..vertices.len())
+		.map(|i| wall(vertices[i], vertices[(i + 1) % vertices.len()], sense))
+		.collect()
+}
+
+fn boundary() -> Vec<WallBase> {
+	let corners = [
+		Point::new(0.0, 0.0),
+		Point::new(SIZE, 0.0),
+		Point::new(SIZE, SIZE),
+		Point::new(0.0, SIZE),
+	];
+	(0..corners.len())
+		.map(|i| {
+			wall(
+				corners[i],
+				corners[(i + 1) % corners.len()],
+				WallSenseType::NORMAL,
+			)
+		})
+		.collect()
+}
+
+// Coordinates are rounded like the ones of walls placed in Foundry
+fn wall(p1: Point, p2: Point, sense: WallSenseType) -> WallBase {
+	WallBase::new(
+		Point::new(p1.x.round(), p1.y.round()),
+		Point::new(p2.x.round(), p2.y.round()),
+		WallSenseType::NORMAL,
+		sense,
+		WallSenseType::NORMAL,
+		DoorType::NONE,
+		DoorState::CLOSED,
+		WallDirection::BOTH,
+		WallHeight::default(),
+		None,
+	)
+}
+
+// Adds sources in the central area of the scene with every variation of radius and vision angle
+fn scene(walls: Vec<WallBase>, rng: &mut Rng) -> SceneSnapshot {
+	let distance = SIZE * 2.0f64.sqrt();
+	let mut sources = vec![];
+	for _ in 0..4 {
+		// Unlike wall coordinates the origin isn't rounded, so it doesn't end up on a wall
+		let origin = Point::new(
+			rng.range(SIZE / 4.0, SIZE * 3.0 / 4.0),
+			rng.range(SIZE / 4.0, SIZE * 3.0 / 4.0),
+		);
+		for &(radius, angle) in &VARIATIONS {
+			sources.push(PolygonSource {
+				origin,
+				height: 0.0,
+				radius: radius.unwrap_or(distance),
+				distance,
+				density: 6.0,
+				angle,
+				rotation: rng.range(0.0, 360.0),
+				polygon_type: PolygonType::SIGHT,
+			});
+		}
+	}
+	SceneSnapshot {
+		wall_ids: vec![None; walls.len()],
+		walls,
+		roofs: vec![],
+		tile_ids: vec![],
+		sources,
+	}
+}
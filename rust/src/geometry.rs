@@ -1,19 +1,9 @@
 use std::f64::consts::PI;
 use std::hash::{Hash, Hasher};
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
-extern "C" {
-	pub type JsPoint;
-
-	#[wasm_bindgen(method, getter)]
-	fn x(this: &JsPoint) -> f64;
-
-	#[wasm_bindgen(method, getter)]
-	fn y(this: &JsPoint) -> f64;
-}
-
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Point {
 	pub x: f64,
@@ -29,48 +19,72 @@ impl Hash for Point {
 
 impl Eq for Point {}
 
-impl From<&JsPoint> for Point {
-	fn from(point: &JsPoint) -> Self {
-		Self::new(point.x(), point.y())
-	}
-}
-
 impl Point {
 	pub fn new(x: f64, y: f64) -> Self {
 		Self { x, y }
 	}
 
-	pub fn from_line_x(line: &Line, x: f64) -> Self {
-		let y = line.calc_y(x);
-		Self { x, y }
-	}
-
 	pub fn distance_to(&self, other: &Self) -> f64 {
 		(self.x - other.x).hypot(self.y - other.y)
 	}
 
+	pub fn distance_to_segment(&self, p1: &Self, p2: &Self) -> f64 {
+		let dx = p2.x - p1.x;
+		let dy = p2.y - p1.y;
+		let length_squared = dx * dx + dy * dy;
+		if length_squared == 0.0 {
+			return self.distance_to(p1);
+		}
+		// Project the point onto the segment and clamp the projection to the segment's ends
+		let t = (((self.x - p1.x) * dx + (self.y - p1.y) * dy) / length_squared).clamp(0.0, 1.0);
+		self.distance_to(&Point::new(p1.x + t * dx, p1.y + t * dy))
+	}
+
 	pub fn is_same_as(&self, other: &Self) -> bool {
 		let e = 0.000001;
 		(self.x - other.x).abs() < e && (self.y - other.y).abs() < e
 	}
 }
 
+impl From<Point> for robust::Coord<f64> {
+	fn from(point: Point) -> Self {
+		Self {
+			x: point.x,
+			y: point.y,
+		}
+	}
+}
+
+/// Determines on which side of the line through `a` and `b` the point `c` lies.
+/// The result is positive if `a`, `b` and `c` are ordered counter clockwise (in a coordinate system where y points up),
+/// negative if they are ordered clockwise and zero if they are collinear.
+/// The sign of the result is exact, even if the magnitude isn't.
+pub fn orient2d(a: Point, b: Point, c: Point) -> f64 {
+	robust::orient2d(a.into(), b.into(), c.into())
+}
+
+/// Checks if two results of `orient2d` put their points on opposite sides of the line
+pub fn is_opposite_side(side1: f64, side2: f64) -> bool {
+	(side1 < 0.0 && side2 > 0.0) || (side1 > 0.0 && side2 < 0.0)
+}
+
+/// Checks if the segments a1-a2 and b1-b2 cross each other in a single point that lies inside of both segments.
+/// Segments that only touch each other or that are collinear don't cross.
+pub fn segments_cross(a1: Point, a2: Point, b1: Point, b2: Point) -> bool {
+	is_opposite_side(orient2d(a1, a2, b1), orient2d(a1, a2, b2))
+		&& is_opposite_side(orient2d(b1, b2, a1), orient2d(b1, b2, a2))
+}
+
+/// An infinite line through two points
 #[derive(Debug, Copy, Clone)]
 pub struct Line {
-	pub m: f64,
-	pub b: f64,
 	pub p1: Point,
+	pub p2: Point,
 }
 
 impl Line {
-	pub fn new(m: f64, b: f64, p1: Point) -> Self {
-		Self { m, b, p1 }
-	}
-
 	pub fn from_points(p1: Point, p2: Point) -> Self {
-		let m = (p1.y - p2.y) / (p1.x - p2.x);
-		let b = p1.y - m * p1.x;
-		Self { m, b, p1 }
+		Self { p1, p2 }
 	}
 
 	pub fn from_point_and_angle(p1: Point, angle: f64) -> Self {
@@ -81,77 +95,99 @@ impl Line {
 		Line::from_points(p1, p2)
 	}
 
-	pub fn empty() -> Self {
-		Self {
-			m: 0.0,
-			b: 0.0,
-			p1: Point::new(0.0, 0.0),
-		}
+	/// A line through `p` that is exactly horizontal
+	pub fn horizontal(p: Point) -> Self {
+		// Adding the magnitude of x makes sure the second point differs from the first, even for huge coordinates
+		Line::from_points(p, Point::new(p.x + p.x.abs() + 1.0, p.y))
 	}
 
-	pub fn is_vertical(&self) -> bool {
-		self.m.is_infinite()
+	/// See `orient2d`
+	pub fn side_of(&self, point: Point) -> f64 {
+		orient2d(self.p1, self.p2, point)
 	}
 
-	pub fn is_horizontal(&self) -> bool {
-		self.m == 0.0
-	}
-
-	pub fn calc_x(&self, y: f64) -> f64 {
-		(y - self.b) / self.m
-	}
-
-	pub fn calc_y(&self, x: f64) -> f64 {
-		self.m * x + self.b
+	/// The position of the projection of `point` onto the line, where 0 is `p1` and 1 is `p2`
+	pub fn position_of(&self, point: Point) -> f64 {
+		let dx = self.p2.x - self.p1.x;
+		let dy = self.p2.y - self.p1.y;
+		((point.x - self.p1.x) * dx + (point.y - self.p1.y) * dy) / (dx * dx + dy * dy)
 	}
 
+	/// The intersection of both lines. The point is located on `other` as precisely as possible.
+	/// If a point defining `other` lies on this line, exactly that point is returned.
+	/// Returns `None` if the lines are parallel or one of them is degenerate.
 	pub fn intersection(&self, other: &Line) -> Option<Point> {
-		// Are both lines vertical?
-		if self.is_vertical() && other.is_vertical() {
+		if self.p1 == self.p2 {
 			return None;
 		}
-
-		// Are the lines paralell?
-		if (self.m - other.m).abs() < 0.00000005 {
+		let side1 = self.side_of(other.p1);
+		let side2 = self.side_of(other.p2);
+		if side1 == side2 {
 			return None;
 		}
-
-		// Is one of the lines vertical?
-		if self.is_vertical() || other.is_vertical() {
-			let vertical;
-			let regular;
-			if self.is_vertical() {
-				vertical = self;
-				regular = other;
-			} else {
-				vertical = other;
-				regular = self;
-			}
-			return Some(Point::from_line_x(&regular, vertical.p1.x));
+		if side1 == 0.0 {
+			return Some(other.p1);
+		}
+		if side2 == 0.0 {
+			return Some(other.p2);
 		}
+		let t = side1 / (side1 - side2);
+		Some(Point::new(
+			other.p1.x + (other.p2.x - other.p1.x) * t,
+			other.p1.y + (other.p2.y - other.p1.y) * t,
+		))
+	}
+}
 
-		// Calculate x coordinate of intersection point between both lines
-		// Find intersection point: x * m1 + b1 = x * m2 + b2
-		// Solve for x: x = (b1 - b2) / (m2 - m1)
-		let x = (self.b - other.b) / (other.m - self.m);
-		if self.m.abs() < other.m.abs() {
-			Some(Point::from_line_x(&self, x))
-		} else {
-			Some(Point::from_line_x(&other, x))
+/// Checks if the point lies inside of the polygon using the even-odd rule. Points on the boundary may go either way.
+pub fn polygon_contains(polygon: &[Point], point: Point) -> bool {
+	let mut inside = false;
+	for (i, p1) in polygon.iter().enumerate() {
+		let p2 = polygon[(i + 1) % polygon.len()];
+		if (p1.y > point.y) != (p2.y > point.y)
+			&& point.x < (p2.x - p1.x) * (point.y - p1.y) / (p2.y - p1.y) + p1.x
+		{
+			inside = !inside;
 		}
 	}
+	inside
+}
 
-	pub fn get_perpendicular_through_point(&self, p: Point) -> Self {
-		let m = -1.0 / self.m;
-		let b = p.y - m * p.x;
-		Self { m, b, p1: p }
+pub fn distance_to_polygon_boundary(polygon: &[Point], point: Point) -> f64 {
+	polygon
+		.iter()
+		.enumerate()
+		.map(|(i, p1)| point.distance_to_segment(p1, &polygon[(i + 1) % polygon.len()]))
+		.fold(f64::INFINITY, f64::min)
+}
+
+// Finds the intersection of the segments a1-a2 and b1-b2.
+// The result is the position of the intersection on both segments, where 0 is the segment's first point and 1 is the segment's second point.
+// Returns `None` if the segments are parallel.
+pub fn segment_intersection_parameters(
+	a1: Point,
+	a2: Point,
+	b1: Point,
+	b2: Point,
+) -> Option<(f64, f64)> {
+	let a = Point::new(a2.x - a1.x, a2.y - a1.y);
+	let b = Point::new(b2.x - b1.x, b2.y - b1.y);
+	let denominator = a.x * b.y - a.y * b.x;
+	if denominator == 0.0 {
+		return None;
 	}
+	let offset = Point::new(b1.x - a1.x, b1.y - a1.y);
+	let t = (offset.x * b.y - offset.y * b.x) / denominator;
+	let u = (offset.x * a.y - offset.y * a.x) / denominator;
+	Some((t, u))
 }
 
 #[derive(Copy, Clone)]
 pub struct CircleIntersection {
 	pub point: Point,
 	pub angle: f64,
+	/// The position of the intersection on the line, where 0 is the line's `p1` and 1 is it's `p2`
+	pub position: f64,
 }
 
 #[derive(Copy, Clone)]
@@ -165,8 +201,15 @@ impl Circle {
 	pub fn intersections(&self, line: &Line) -> Option<(CircleIntersection, CircleIntersection)> {
 		// We first seach for the closest point of the line to the center.
 		// If intersections exist, that is the halfway point between both intersections
-		let perpendicular = line.get_perpendicular_through_point(self.center);
-		let closest_point = perpendicular.intersection(line).unwrap();
+		// Degenerate lines don't have a direction and therefore can't intersect the circle
+		if line.p1 == line.p2 {
+			return None;
+		}
+		let t = line.position_of(self.center);
+		let closest_point = Point::new(
+			line.p1.x + (line.p2.x - line.p1.x) * t,
+			line.p1.y + (line.p2.y - line.p1.y) * t,
+		);
 
 		// Calculate how far the closest point on the line is away from the circles center
 		let closest_distance = self.center.distance_to(&closest_point);
@@ -198,9 +241,14 @@ impl Circle {
 			// This happens if the line goes through the circles center.
 
 			// Calculate the angle from the circle center + a point on the line
-			// Taking p1 is risky, because the point could be the circle center itself. That should never happen for Lichtgeschwindigkeit though
-			assert_ne!(self.center, line.p1);
-			intersection1_angle = (self.center.y - line.p1.y).atan2(self.center.x - line.p1.x);
+			// p1 may be the circle center itself, in which case we take a different point on the line
+			let point_on_line = if self.center != line.p1 {
+				line.p1
+			} else {
+				line.p2
+			};
+			intersection1_angle =
+				(self.center.y - point_on_line.y).atan2(self.center.x - point_on_line.x);
 			intersection2_angle = intersection1_angle + PI;
 		}
 
@@ -211,6 +259,7 @@ impl Circle {
 				self.center.y - intersection1_angle.sin() * self.radius,
 			),
 			angle: intersection1_angle,
+			position: 0.0,
 		};
 
 		// Mirror intersection 1 along the perpendicular to find intersection 2
@@ -220,7 +269,10 @@ impl Circle {
 				closest_point.y - (intersection1.point.y - closest_point.y),
 			),
 			angle: intersection2_angle,
+			position: 0.0,
 		};
+		intersection1.position = line.position_of(intersection1.point);
+		intersection2.position = line.position_of(intersection2.point);
 
 		// Normalize the intersection angles
 		if intersection1.angle > PI {
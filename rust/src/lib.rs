@@ -1,10 +1,17 @@
-mod geometry;
+pub mod benchmark;
+pub mod geometry;
+pub mod minimize;
 mod ptr_indexed_hash_set;
-mod raycasting;
-mod serialization;
+pub mod raycasting;
+pub mod serialization;
+pub mod svg;
+#[cfg(test)]
+mod tests;
 
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(start)]
 pub fn main() {
 	std::panic::set_hook(Box::new(console_error_panic_hook::hook));
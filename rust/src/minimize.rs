@@ -0,0 +1,180 @@
+use crate::geometry::{distance_to_polygon_boundary, polygon_contains, segments_cross, Point};
+use crate::raycasting::{Internals, PolygonResult};
+use crate::serialization::{is_same_polygon, with_ids, RaycastingCall, TestCase};
+#[cfg(not(target_arch = "wasm32"))]
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+// Walls that are closer to the LOS than this are considered to be visible
+const TOLERANCE: f64 = 0.1;
+
+/// The kind of failure a reproducer needs to keep
+pub enum Failure {
+	/// Computing the polygons returns an error or invalid polygons. On native targets panics count as well,
+	/// on wasm32 a panic aborts the minimization.
+	Error,
+	/// The computed polygons differ from the given known-good polygons
+	Mismatch { los: Vec<Point>, fov: Vec<Point> },
+}
+
+impl Failure {
+	/// Checks if the call still fails in this way
+	pub fn occurs(&self, call: &RaycastingCall) -> bool {
+		match (self, compute(call)) {
+			(Self::Error, result) => !matches!(result, Some(Ok(_))),
+			(Self::Mismatch { los, fov }, Some(Ok((actual_los, actual_fov)))) => {
+				!is_same_polygon(los, &actual_los) || !is_same_polygon(fov, &actual_fov)
+			}
+			// A call that doesn't produce polygons anymore fails differently
+			(Self::Mismatch { .. }, _) => false,
+		}
+	}
+}
+
+/// Removes walls and roofs from the call as long as the failure still occurs and returns the smallest call that was found
+/// as a test case. Returns `None` if the original call doesn't fail.
+///
+/// For mismatches only walls that are completely hidden from the source are removed, because the known-good polygons
+/// remain correct only for those. Erroneous calls don't have a correct result yet, so their test case has empty
+/// polygons, which need to be replaced using `generateTest` once the bug has been fixed.
+///
+/// On native targets panics are caught to check whether a call fails. The panic hook still runs, so the panics show up
+/// in the output. Panics can't be caught on wasm32, so calls that panic need to be minimized natively.
+pub fn minimize(call: RaycastingCall, failure: &Failure) -> Option<TestCase> {
+	if !failure.occurs(&call) {
+		return None;
+	}
+	let removable = call
+		.walls
+		.iter()
+		.enumerate()
+		.filter(|(_, wall)| match failure {
+			Failure::Error => true,
+			Failure::Mismatch { los, .. } => !touches_polygon(los, wall.p1, wall.p2),
+		})
+		.map(|(i, _)| i)
+		.collect::<Vec<_>>();
+	let kept = delta_debug(removable.clone(), |candidate| {
+		failure.occurs(&without_walls(&call, &removable, candidate))
+	});
+	let call = remove_unused_roofs(without_walls(&call, &removable, &kept));
+	let (los, fov) = match failure {
+		Failure::Error => (vec![], vec![]),
+		Failure::Mismatch { los, fov } => (los.clone(), fov.clone()),
+	};
+	Some(TestCase { call, los, fov })
+}
+
+// Zeller's ddmin: finds a subset of `items` for which `fails` still holds, such that removing any single item makes it pass
+fn delta_debug<F: FnMut(&[usize]) -> bool>(mut items: Vec<usize>, mut fails: F) -> Vec<usize> {
+	if fails(&[]) {
+		return vec![];
+	}
+	let mut granularity = 2;
+	while items.len() >= 2 {
+		let chunk_size = items.len().div_ceil(granularity);
+		let chunks = items.chunks(chunk_size).collect::<Vec<_>>();
+		let mut reduced = None;
+		for (i, chunk) in chunks.iter().enumerate() {
+			if fails(chunk) {
+				reduced = Some((chunk.to_vec(), 2));
+				break;
+			}
+			let complement = chunks
+				.iter()
+				.enumerate()
+				.filter(|(j, _)| *j != i)
+				.flat_map(|(_, chunk)| chunk.iter().copied())
+				.collect::<Vec<_>>();
+			if fails(&complement) {
+				reduced = Some((complement, (granularity - 1).max(2)));
+				break;
+			}
+		}
+		match reduced {
+			Some((subset, new_granularity)) => {
+				items = subset;
+				granularity = new_granularity;
+			}
+			None if granularity >= items.len() => break,
+			None => granularity = (granularity * 2).min(items.len()),
+		}
+	}
+	items
+}
+
+// Keeps all walls that aren't removable and the removable walls in `kept`
+fn without_walls(call: &RaycastingCall, removable: &[usize], kept: &[usize]) -> RaycastingCall {
+	let mut keep = vec![true; call.walls.len()];
+	for &i in removable {
+		keep[i] = false;
+	}
+	for &i in kept {
+		keep[i] = true;
+	}
+	let (walls, wall_ids) = with_ids(&call.walls, &call.wall_ids)
+		.into_iter()
+		.zip(keep)
+		.filter(|(_, keep)| *keep)
+		.map(|(wall, _)| wall)
+		.unzip();
+	RaycastingCall {
+		walls,
+		wall_ids,
+		roofs: call.roofs.clone(),
+		tile_ids: call.tile_ids.clone(),
+		..*call
+	}
+}
+
+fn remove_unused_roofs(mut call: RaycastingCall) -> RaycastingCall {
+	let mut new_ids = vec![None; call.roofs.len()];
+	let mut roofs = Vec::new();
+	let mut tile_ids = Vec::new();
+	let old_roofs = with_ids(&call.roofs, &call.tile_ids);
+	for wall in &mut call.walls {
+		if let Some(roof) = wall.roof {
+			let new_id = *new_ids[roof].get_or_insert_with(|| {
+				let (occluded, id) = old_roofs[roof].clone();
+				roofs.push(occluded);
+				tile_ids.push(id);
+				roofs.len() - 1
+			});
+			wall.roof = Some(new_id);
+		}
+	}
+	call.roofs = roofs;
+	call.tile_ids = tile_ids;
+	call
+}
+
+// `None` if the computation panicked. Invalid polygons are reported as errors.
+#[cfg(not(target_arch = "wasm32"))]
+fn compute(call: &RaycastingCall) -> Option<PolygonResult> {
+	catch_unwind(AssertUnwindSafe(|| compute_validated(call))).ok()
+}
+
+// wasm32 aborts on panics, so there's nothing to catch
+#[cfg(target_arch = "wasm32")]
+fn compute(call: &RaycastingCall) -> Option<PolygonResult> {
+	Some(compute_validated(call))
+}
+
+fn compute_validated(call: &RaycastingCall) -> PolygonResult {
+	let mut internals = Internals {
+		validate: true,
+		..Internals::default()
+	};
+	call.compute_with_internals(&call.build_cache(), Some(&mut internals))
+}
+
+// Walls that don't touch the LOS are hidden from the source and can't change the correct result
+fn touches_polygon(polygon: &[Point], p1: Point, p2: Point) -> bool {
+	polygon_contains(polygon, p1)
+		|| polygon_contains(polygon, p2)
+		|| distance_to_polygon_boundary(polygon, p1) < TOLERANCE
+		|| distance_to_polygon_boundary(polygon, p2) < TOLERANCE
+		|| polygon.iter().enumerate().any(|(i, q1)| {
+			let q2 = polygon[(i + 1) % polygon.len()];
+			segments_cross(p1, p2, *q1, q2) || q1.distance_to_segment(&p1, &p2) < TOLERANCE
+		})
+}
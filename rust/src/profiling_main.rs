@@ -1,34 +1,428 @@
-mod geometry;
-mod ptr_indexed_hash_set;
-mod raycasting;
-mod serialization;
-#[cfg(test)]
-mod tests;
+use lichtgeschwindigkeit::benchmark::{measure, synthetic_scenes, Measurement};
+use lichtgeschwindigkeit::minimize::{minimize, Failure};
+use lichtgeschwindigkeit::serialization::*;
+use lichtgeschwindigkeit::svg::render_svg;
 
-use raycasting::*;
-use serialization::*;
+use serde_json::json;
+use std::env;
+use std::fs::{read_dir, read_to_string, write};
+use std::io::{stdin, Read};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::{Duration, Instant};
 
-use std::fs::read_to_string;
+const USAGE: &str = "Usage: lichtgeschwindigkeit-profiling <command> [arguments]
+
+Inputs are read from a file or from stdin if the file is `-`.
+Calls and test cases may be given as ascii85 (as printed by `serializeData` and `generateTest`) or as JSON.
+
+Commands:
+  decode <call>                   Print the call as JSON
+  encode <call>                   Print the call as ascii85
+  decode-test <test>              Print the test case as JSON
+  encode-test <test>              Print the test case as ascii85
+  run <call>                      Compute the polygons and print them as JSON
+  bench <call> [iterations]       Compute the polygons repeatedly and print timing statistics
+  gen-test <call>                 Compute the polygons and print them together with the call as an ascii85 test case
+  minimize <call> [expected]      Remove walls and roofs that aren't needed to reproduce the failure and print the
+                                  smallest call as an ascii85 test case. With a known-good test case of the call as
+                                  `expected`, any other result is a failure, otherwise only panics and errors are
+  svg <call>                      Draw the scene and the computed polygons as SVG
+  import <export>                 Read a scene exported from Foundry and print its walls and roofs as a scene snapshot
+                                  in JSON, to which sources can be added
+  replay <scene>                  Compute the polygons of all sources of a scene snapshot and print them as JSON
+  gen-scene-test <scene>          Compute the polygons of all sources and print them together with the scene
+                                  as an ascii85 scene test case
+  bench-suite [options]           Time building the cache and the phases of the polygon computation for the test
+                                  cases and for large synthetic scenes. Build with --release for meaningful timings.
+                                  Options:
+                                    --tests <directory>   Where the test cases are read from (default: tests)
+                                    --iterations <n>      How often each scene is computed (default: 10)
+                                    --save <baseline>     Store the timings as JSON to compare later runs to them
+                                    --compare <baseline>  Print the change of every timing relative to the baseline
+  verify [directory]              Check the .ascii85 and .json test cases in the directory (default: tests)
+                                  and the scene test cases in its `scenes` subdirectory.
+                                  An SVG of every failing test case is written next to it.";
 
 fn main() {
-	let data = deserialize_ascii85::<RaycastingCall>(&read_to_string("data.txt").unwrap());
-	let cache = Cache::build(data.walls, TileCache::from_roofs(data.roofs));
-	let mut sum = 0;
-	let mut los = None;
-	for _i in 0..1 {
-		los = Some(compute_polygon(
-			&cache,
-			data.origin,
-			data.height,
-			data.radius,
-			data.distance,
-			data.density,
-			VisionAngle::from_rotation_and_angle(data.rotation, data.angle, data.origin),
-			PolygonType::SIGHT,
-			None,
+	let args = env::args().skip(1).collect::<Vec<_>>();
+	let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+	let result = match args.as_slice() {
+		["decode", input] => read_call(input).map(|call| println!("{}", serialize_json(&call))),
+		["encode", input] => read_call(input).map(|call| println!("{}", serialize_ascii85(call))),
+		["decode-test", input] => {
+			read::<TestCase>(input).map(|test| println!("{}", serialize_json(&test)))
+		}
+		["encode-test", input] => {
+			read::<TestCase>(input).map(|test| println!("{}", serialize_ascii85(test)))
+		}
+		["run", input] => read_call(input).and_then(|call| run(&call)),
+		["bench", input] => read_call(input).and_then(|call| bench(&call, 100)),
+		["bench", input, iterations] => match iterations.parse() {
+			Ok(iterations) if iterations > 0 => {
+				read_call(input).and_then(|call| bench(&call, iterations))
+			}
+			_ => Err(format!("Invalid number of iterations: {}", iterations)),
+		},
+		["gen-test", input] => read_call(input).and_then(generate_test),
+		["minimize", input] => read_call(input).and_then(|call| minimize_call(call, None)),
+		["minimize", input, expected] => read_call(input).and_then(|call| {
+			read::<TestCase>(expected).and_then(|expected| minimize_call(call, Some(expected)))
+		}),
+		["svg", input] => read_call(input).map(|call| print!("{}", render_svg(&call, None))),
+		["import", input] => import(input),
+		["replay", input] => read::<SceneSnapshot>(input).map(|scene| replay(&scene)),
+		["gen-scene-test", input] => read::<SceneSnapshot>(input).and_then(generate_scene_test),
+		["bench-suite", options @ ..] => bench_suite(options),
+		["verify"] => verify(Path::new("tests")),
+		["verify", directory] => verify(Path::new(directory)),
+		_ => {
+			eprintln!("{}", USAGE);
+			exit(2);
+		}
+	};
+	if let Err(error) = result {
+		eprintln!("{}", error);
+		exit(1);
+	}
+}
+
+fn read_call(input: &str) -> Result<RaycastingCall, String> {
+	read(input)
+}
+
+fn read<T: Serialize + Json>(input: &str) -> Result<T, String> {
+	let data = read_input(input)?;
+	deserialize_any(&data).map_err(|error| format!("Couldn't read {}: {}", input, error))
+}
+
+fn read_input(input: &str) -> Result<String, String> {
+	if input == "-" {
+		let mut data = String::new();
+		stdin()
+			.read_to_string(&mut data)
+			.map_err(|error| format!("Couldn't read stdin: {}", error))?;
+		Ok(data)
+	} else {
+		read_to_string(input).map_err(|error| format!("Couldn't read {}: {}", input, error))
+	}
+}
+
+fn import(input: &str) -> Result<(), String> {
+	// Exports only contain wall heights if the Wall Height module was used, so they can always be read
+	let cache = import_foundry_scene(&read_input(input)?, true)
+		.map_err(|error| format!("Couldn't import {}: {}", input, error))?;
+	println!("{}", serialize_json(&SceneSnapshot::new(&cache, vec![])));
+	Ok(())
+}
+
+fn run(call: &RaycastingCall) -> Result<(), String> {
+	let (los, fov) = call
+		.compute(&call.build_cache())
+		.map_err(|error| error.to_string())?;
+	let output = json!({
+		"los": los.to_json(),
+		"fov": fov.to_json(),
+	});
+	println!("{}", serde_json::to_string_pretty(&output).unwrap());
+	Ok(())
+}
+
+fn bench(call: &RaycastingCall, iterations: usize) -> Result<(), String> {
+	let start = Instant::now();
+	let cache = call.build_cache();
+	let build_time = start.elapsed();
+
+	let mut times = Vec::with_capacity(iterations);
+	for _ in 0..iterations {
+		let start = Instant::now();
+		call.compute(&cache).map_err(|error| error.to_string())?;
+		times.push(start.elapsed());
+	}
+	times.sort();
+	let total = times.iter().sum::<Duration>();
+	println!("Walls:        {}", call.walls.len());
+	println!("Cache build:  {:?}", build_time);
+	println!("Iterations:   {}", iterations);
+	println!("Min:          {:?}", times[0]);
+	println!("Median:       {:?}", times[times.len() / 2]);
+	println!("Mean:         {:?}", total / iterations as u32);
+	println!("Max:          {:?}", times[times.len() - 1]);
+	println!("Total:        {:?}", total);
+	Ok(())
+}
+
+fn bench_suite(options: &[&str]) -> Result<(), String> {
+	let mut directory = "tests";
+	let mut iterations = 10;
+	let mut save = None;
+	let mut baseline = None;
+	let mut options = options.iter();
+	while let Some(option) = options.next() {
+		let mut value = || {
+			options
+				.next()
+				.copied()
+				.ok_or_else(|| format!("Missing value of {}", option))
+		};
+		match *option {
+			"--tests" => directory = value()?,
+			"--iterations" => {
+				let value = value()?;
+				iterations = match value.parse() {
+					Ok(iterations) if iterations > 0 => iterations,
+					_ => return Err(format!("Invalid number of iterations: {}", value)),
+				}
+			}
+			"--save" => save = Some(value()?),
+			"--compare" => baseline = Some(read_baseline(value()?)?),
+			_ => return Err(format!("Unknown option {}\n\n{}", option, USAGE)),
+		}
+	}
+
+	let mut scenes = vec![];
+	for path in test_files(Path::new(directory))? {
+		let call = read::<TestCase>(path.to_str().unwrap())?.call;
+		let scene = SceneSnapshot {
+			sources: vec![call.source()],
+			walls: call.walls,
+			wall_ids: call.wall_ids,
+			roofs: call.roofs,
+			tile_ids: call.tile_ids,
+		};
+		scenes.push((
+			path.file_stem().unwrap().to_string_lossy().into_owned(),
+			scene,
 		));
-		sum += los.as_ref().unwrap().0.len();
 	}
+	let scene_directory = Path::new(directory).join("scenes");
+	if scene_directory.is_dir() {
+		for path in test_files(&scene_directory)? {
+			let scene = read::<SceneTestCase>(path.to_str().unwrap())?.scene;
+			let name = format!("scenes/{}", path.file_stem().unwrap().to_string_lossy());
+			scenes.push((name, scene));
+		}
+	}
+	scenes.extend(
+		synthetic_scenes()
+			.into_iter()
+			.map(|(name, scene)| (name.to_owned(), scene)),
+	);
 
-	println!("{:#?}, {}", los.unwrap().0.len(), sum);
+	println!(
+		"{:<48} {:>6} {:>7} {:>16} {:>16} {:>16} {:>16} {:>16} {:>16}",
+		"Scene (times in ms)",
+		"Walls",
+		"Sources",
+		"build",
+		"prepare_data",
+		"calculate_los",
+		"calculate_fov",
+		"fill_gaps",
+		"total"
+	);
+	let mut results = serde_json::Map::new();
+	for (name, scene) in &scenes {
+		let measurement =
+			measure(scene, iterations).map_err(|error| format!("{}: {}", name, error))?;
+		let timings = timings(&measurement);
+		print!(
+			"{:<48} {:>6} {:>7}",
+			name,
+			scene.walls.len(),
+			scene.sources.len()
+		);
+		for (metric, duration) in &timings {
+			let milliseconds = duration.as_secs_f64() * 1000.0;
+			let previous = baseline
+				.as_ref()
+				.and_then(|baseline| baseline.get(name))
+				.and_then(|timings| timings.get(*metric))
+				.and_then(serde_json::Value::as_f64);
+			match (&baseline, previous) {
+				(None, _) => print!(" {:>16.3}", milliseconds),
+				(Some(_), Some(previous)) => print!(
+					" {:>9.3} {:>+5.0}%",
+					milliseconds,
+					(milliseconds / previous - 1.0) * 100.0
+				),
+				(Some(_), None) => print!(" {:>9.3}    new", milliseconds),
+			}
+		}
+		println!();
+		let timings = timings
+			.iter()
+			.map(|(metric, duration)| (metric.to_string(), json!(duration.as_secs_f64() * 1000.0)))
+			.collect();
+		results.insert(name.clone(), serde_json::Value::Object(timings));
+	}
+
+	if let Some(save) = save {
+		let json = serde_json::to_string_pretty(&results).unwrap();
+		write(save, json).map_err(|error| format!("Couldn't write {}: {}", save, error))?;
+	}
+	Ok(())
+}
+
+// The baseline maps each scene to its timings in milliseconds
+fn read_baseline(path: &str) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+	let json = read_input(path)?;
+	match serde_json::from_str(&json) {
+		Ok(serde_json::Value::Object(baseline)) => Ok(baseline),
+		_ => Err(format!("{} isn't a baseline saved by bench-suite", path)),
+	}
+}
+
+fn timings(measurement: &Measurement) -> [(&'static str, Duration); 6] {
+	[
+		("build", measurement.build),
+		("prepare_data", measurement.phases.prepare_data),
+		("calculate_los", measurement.phases.calculate_los),
+		("calculate_fov", measurement.phases.calculate_fov),
+		("fill_gaps", measurement.phases.fill_gaps),
+		("total", measurement.total()),
+	]
+}
+
+fn generate_test(call: RaycastingCall) -> Result<(), String> {
+	let (los, fov) = call
+		.compute(&call.build_cache())
+		.map_err(|error| error.to_string())?;
+	println!("{}", serialize_ascii85(TestCase { call, los, fov }));
+	Ok(())
+}
+
+fn minimize_call(call: RaycastingCall, expected: Option<TestCase>) -> Result<(), String> {
+	let failure = match expected {
+		Some(expected) => Failure::Mismatch {
+			los: expected.los,
+			fov: expected.fov,
+		},
+		None => Failure::Error,
+	};
+	let reproducer = minimize(call, &failure).ok_or("The call doesn't fail")?;
+	println!("{}", serialize_ascii85(reproducer));
+	Ok(())
+}
+
+fn replay(scene: &SceneSnapshot) {
+	let output = scene
+		.compute(&scene.build_cache())
+		.into_iter()
+		.map(|result| match result {
+			Ok((los, fov)) => json!({
+				"los": los.to_json(),
+				"fov": fov.to_json(),
+			}),
+			Err(error) => json!({ "error": error.to_string() }),
+		})
+		.collect::<Vec<_>>();
+	println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+fn generate_scene_test(scene: SceneSnapshot) -> Result<(), String> {
+	let test = SceneTestCase::generate(scene).map_err(|error| error.to_string())?;
+	println!("{}", serialize_ascii85(test));
+	Ok(())
+}
+
+fn verify(directory: &Path) -> Result<(), String> {
+	let mut tests = test_files(directory)?
+		.into_iter()
+		.map(|path| (path, false))
+		.collect::<Vec<_>>();
+	let scene_directory = directory.join("scenes");
+	if scene_directory.is_dir() {
+		tests.extend(
+			test_files(&scene_directory)?
+				.into_iter()
+				.map(|path| (path, true)),
+		);
+	}
+
+	let mut failures = 0;
+	for (path, is_scene) in &tests {
+		let name = path.file_stem().unwrap().to_string_lossy();
+		let result = if *is_scene {
+			verify_scene_test(path)
+		} else {
+			verify_test(path)
+		};
+		match result {
+			Ok(()) => println!("ok      {}", name),
+			Err(error) => {
+				failures += 1;
+				println!("FAILED  {}: {}", name, error);
+			}
+		}
+	}
+	println!("{} passed, {} failed", tests.len() - failures, failures);
+	if failures > 0 {
+		return Err(format!("{} test cases failed", failures));
+	}
+	Ok(())
+}
+
+fn test_files(directory: &Path) -> Result<Vec<PathBuf>, String> {
+	let mut paths = read_dir(directory)
+		.map_err(|error| format!("Couldn't read {}: {}", directory.display(), error))?
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| {
+			path.extension()
+				.is_some_and(|extension| extension == "ascii85" || extension == "json")
+		})
+		.collect::<Vec<_>>();
+	paths.sort();
+	Ok(paths)
+}
+
+fn verify_test(path: &Path) -> Result<(), String> {
+	let test = read::<TestCase>(path.to_str().unwrap())?;
+	let result = check_test(&test);
+	if result.is_err() {
+		let svg = render_svg(&test.call, Some((&test.los, &test.fov)));
+		write(path.with_extension("svg"), svg).map_err(|error| error.to_string())?;
+	}
+	result
+}
+
+// Only the first failing source is drawn, it's usually the one to look at
+fn verify_scene_test(path: &Path) -> Result<(), String> {
+	let scene_test = read::<SceneTestCase>(path.to_str().unwrap())?;
+	if scene_test.polygons.len() != scene_test.scene.sources.len() {
+		return Err(format!(
+			"The scene has {} sources, but there are polygons for {}",
+			scene_test.scene.sources.len(),
+			scene_test.polygons.len()
+		));
+	}
+	for source in 0..scene_test.scene.sources.len() {
+		let test = scene_test.test_case(source);
+		if let Err(error) = check_test(&test) {
+			let svg = render_svg(&test.call, Some((&test.los, &test.fov)));
+			write(path.with_extension("svg"), svg).map_err(|error| error.to_string())?;
+			return Err(format!("Source {}: {}", source, error));
+		}
+	}
+	Ok(())
+}
+
+fn check_test(test: &TestCase) -> Result<(), String> {
+	// A panic in one test case shouldn't prevent the remaining ones from being checked
+	let (los, fov) = catch_unwind(AssertUnwindSafe(|| {
+		test.call.compute(&test.call.build_cache())
+	}))
+	.map_err(|_| "Panicked".to_owned())?
+	.map_err(|error| error.to_string())?;
+	if !test.matches(&los, &fov) {
+		return Err(format!(
+			"The polygons have {} LOS and {} FOV points and differ from the expected ones with {} and {} points",
+			los.len(),
+			fov.len(),
+			test.los.len(),
+			test.fov.len(),
+		));
+	}
+	Ok(())
 }
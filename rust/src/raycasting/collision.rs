@@ -0,0 +1,49 @@
+use crate::geometry::{segment_intersection_parameters, Point};
+use crate::raycasting::types::{Cache, PolygonType, WallSenseType};
+use crate::raycasting::util::is_smaller_relative;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Collision {
+	pub point: Point,
+	/// The index of the wall in `Cache::walls`. It is only valid until a wall is removed from the cache,
+	/// because that moves the last wall to the freed index. Use `Cache::wall_ids` to keep track of a wall.
+	pub wall: usize,
+}
+
+// Finds the first wall that blocks movement on the straight path from `from` to `to`
+pub fn find_collision(cache: &Cache, from: Point, to: Point, height: f64) -> Option<Collision> {
+	if from == to {
+		return None;
+	}
+	// The angle of the movement as it would be calculated for an endpoint in `compute_polygon`
+	let angle = (from.y - to.y).atan2(from.x - to.x);
+	let mut candidates = Vec::new();
+	cache.grid.segment_candidates(from, to, &mut candidates);
+	let mut closest: Option<(f64, usize)> = None;
+	for index in candidates {
+		let wall = &cache.walls[index];
+		if wall.effective_sense(cache, height, PolygonType::MOVEMENT) == WallSenseType::NONE {
+			continue;
+		}
+		let (t, u) = match segment_intersection_parameters(from, to, wall.p1, wall.p2) {
+			Some(parameters) => parameters,
+			None => continue,
+		};
+		// Walls that touch the starting point don't block, otherwise a token standing on a wall couldn't move away from it
+		if t <= 0.0 || t > 1.0 || !(0.0..=1.0).contains(&u) {
+			continue;
+		}
+		if let Some(see_through_angle) = wall.see_through_angle() {
+			if is_smaller_relative(angle, see_through_angle) {
+				continue;
+			}
+		}
+		if closest.map(|(closest_t, _)| t < closest_t).unwrap_or(true) {
+			closest = Some((t, index));
+		}
+	}
+	closest.map(|(t, wall)| Collision {
+		point: Point::new(from.x + t * (to.x - from.x), from.y + t * (to.y - from.y)),
+		wall,
+	})
+}
@@ -0,0 +1,74 @@
+use crate::geometry::Point;
+use crate::raycasting::PolygonDefect;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The reasons why a polygon couldn't be computed. Wall indices refer to `Cache::walls`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RaycastingError {
+	/// The origin has coordinates that aren't finite
+	InvalidOrigin { origin: Point },
+	/// The density isn't a positive number, so the arcs of the polygons can't be sampled
+	InvalidDensity { density: f64 },
+	/// A property of the source that has no default is missing or isn't a number
+	InvalidSource { property: &'static str },
+	/// The angle of an endpoint relative to the origin isn't a number.
+	/// This happens if the origin or a wall has coordinates that aren't numbers.
+	InvalidAngle { point: Point },
+	/// A ray from the origin doesn't intersect the wall it is supposed to hit
+	MissingWallIntersection { wall: usize },
+	/// A wall crosses a side of the vision cone, but no intersection with that side could be found
+	MissingVisionAngleIntersection { wall: usize },
+	/// An edge of the LOS polygon crosses the radius, but no intersection with the circle could be found
+	MissingCircleIntersection { p1: Point, p2: Point },
+	/// The closest wall vanished before a point of the LOS polygon was found
+	MissingLosPoint { angle: f64 },
+	/// The computed polygons violate an invariant. Only reported if `Internals::validate` is set.
+	InvalidPolygon { defect: PolygonDefect },
+}
+
+impl Display for RaycastingError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidOrigin { origin } => write!(
+				f,
+				"The origin ({}, {}) doesn't have finite coordinates",
+				origin.x, origin.y
+			),
+			Self::InvalidDensity { density } => {
+				write!(f, "The density {} isn't a positive number", density)
+			}
+			Self::InvalidSource { property } => {
+				write!(f, "The source doesn't have a valid {}", property)
+			}
+			Self::InvalidAngle { point } => write!(
+				f,
+				"The angle of the point ({}, {}) relative to the origin isn't a number",
+				point.x, point.y
+			),
+			Self::MissingWallIntersection { wall } => {
+				write!(f, "A ray from the origin doesn't intersect wall {}", wall)
+			}
+			Self::MissingVisionAngleIntersection { wall } => write!(
+				f,
+				"Wall {} doesn't intersect the side of the vision cone it crosses",
+				wall
+			),
+			Self::MissingCircleIntersection { p1, p2 } => write!(
+				f,
+				"The edge from ({}, {}) to ({}, {}) doesn't intersect the radius",
+				p1.x, p1.y, p2.x, p2.y
+			),
+			Self::MissingLosPoint { angle } => write!(
+				f,
+				"No point of the line of sight was found before the closest wall vanished at angle {}",
+				angle
+			),
+			Self::InvalidPolygon { defect } => {
+				write!(f, "The computed polygon is invalid: {}", defect)
+			}
+		}
+	}
+}
+
+impl Error for RaycastingError {}
@@ -0,0 +1,208 @@
+use crate::geometry::{segment_intersection_parameters, Point};
+use crate::raycasting::error::RaycastingError;
+use crate::raycasting::raycasting::compute_polygon;
+use crate::raycasting::types::{Cache, PolygonSource, VisionAngle, WallBase, WallSenseType};
+use crate::raycasting::util::is_smaller_relative;
+use crate::serialization::{serialize_ascii85, RaycastingCall};
+use std::f64::consts::PI;
+
+pub struct FallbackPolygon {
+	pub los: Vec<Point>,
+	pub fov: Vec<Point>,
+	/// The error that made the fallback necessary. `None` if the polygon has been computed regularly.
+	pub error: Option<RaycastingError>,
+	/// The serialized call that caused the error, to be included in bug reports
+	pub call: Option<String>,
+}
+
+impl FallbackPolygon {
+	pub fn used_fallback(&self) -> bool {
+		self.error.is_some()
+	}
+}
+
+/// Computes the polygons like `compute_polygon`. If that fails, the polygons are sampled with rays instead.
+/// The sampled polygons are less precise, but they are still better than no polygons at all.
+/// Only fails if the polygons can't be sampled either, see `sample_polygon`.
+pub fn compute_polygon_with_fallback(
+	cache: &Cache,
+	source: &PolygonSource,
+) -> Result<FallbackPolygon, RaycastingError> {
+	match compute_polygon(
+		cache,
+		source.origin,
+		source.height,
+		source.radius,
+		source.distance,
+		source.density,
+		VisionAngle::from_rotation_and_angle(source.rotation, source.angle, source.origin),
+		source.polygon_type,
+		None,
+	) {
+		Ok((los, fov)) => Ok(FallbackPolygon {
+			los,
+			fov,
+			error: None,
+			call: None,
+		}),
+		Err(error) => {
+			let (los, fov) = sample_polygon(cache, source)?;
+			Ok(FallbackPolygon {
+				los,
+				fov,
+				error: Some(error),
+				call: Some(serialize_ascii85(RaycastingCall::new(cache, source))),
+			})
+		}
+	}
+}
+
+/// Samples the polygons by casting a ray every `density` degrees and using the closest blocking wall for each ray.
+/// This is the fallback of `compute_polygon_with_fallback` for callers that already know that `compute_polygon` fails.
+/// Fails if the density isn't a positive number.
+pub fn sample_polygon(
+	cache: &Cache,
+	source: &PolygonSource,
+) -> Result<(Vec<Point>, Vec<Point>), RaycastingError> {
+	if !source.density.is_finite() || source.density <= 0.0 {
+		return Err(RaycastingError::InvalidDensity {
+			density: source.density,
+		});
+	}
+	let vision_angle =
+		VisionAngle::from_rotation_and_angle(source.rotation, source.angle, source.origin);
+	let origin = source.origin;
+	let walls = relevant_walls(cache, source);
+	// Rays only need to reach the farthest wall, which also keeps them finite if the distance is infinite
+	let ray_length = walls
+		.iter()
+		.flat_map(|(wall, _)| [wall.p1, wall.p2])
+		.map(|point| origin.distance_to(&point))
+		.filter(|distance| distance.is_finite())
+		.fold(1.0, f64::max)
+		.min(source.distance);
+	// Rays that don't hit a wall end at the distance. If it's infinite, they end behind all walls and the radius instead.
+	let open_distance = if source.distance.is_finite() {
+		source.distance
+	} else if source.radius.is_finite() {
+		ray_length.max(source.radius)
+	} else {
+		ray_length
+	};
+	let step = source.density.to_radians();
+
+	let mut angles = Vec::new();
+	let (start, end) = match &vision_angle {
+		Some(vision_angle) if vision_angle.start < vision_angle.end => {
+			(vision_angle.start, vision_angle.end)
+		}
+		Some(vision_angle) => (vision_angle.start, vision_angle.end + 2.0 * PI),
+		None => (-PI, PI),
+	};
+	let mut angle = start;
+	while angle < end {
+		angles.push(angle);
+		angle += step;
+	}
+	if vision_angle.is_some() {
+		angles.push(end);
+	}
+
+	let mut los = Vec::with_capacity(angles.len() + 1);
+	let mut fov = Vec::with_capacity(angles.len() + 1);
+	if vision_angle.is_some() {
+		los.push(origin);
+		fov.push(origin);
+	}
+	for mut angle in angles {
+		if angle > PI {
+			angle -= 2.0 * PI;
+		}
+		let direction = Point::new(-angle.cos(), -angle.sin());
+		let distance = cast_ray(&walls, origin, direction, ray_length, angle)
+			.map(|t| t * ray_length)
+			.unwrap_or(open_distance);
+		los.push(Point::new(
+			origin.x + direction.x * distance,
+			origin.y + direction.y * distance,
+		));
+		let distance = distance.min(source.radius);
+		fov.push(Point::new(
+			origin.x + direction.x * distance,
+			origin.y + direction.y * distance,
+		));
+	}
+	Ok((los, fov))
+}
+
+// Collects the walls that may block a ray of the source within its radius or distance, together with their sense
+fn relevant_walls<'a>(
+	cache: &'a Cache,
+	source: &PolygonSource,
+) -> Vec<(&'a WallBase, WallSenseType)> {
+	let mut candidates = Vec::new();
+	let relevance_radius = source.radius.max(source.distance);
+	if !cache
+		.grid
+		.circle_candidates(source.origin, relevance_radius, &mut candidates)
+	{
+		candidates.extend(0..cache.walls.len());
+	}
+	candidates
+		.into_iter()
+		.map(|index| &cache.walls[index])
+		.filter(|wall| wall.p1 != wall.p2 && wall.p1 != source.origin && wall.p2 != source.origin)
+		.map(|wall| {
+			(
+				wall,
+				wall.effective_sense(cache, source.height, source.polygon_type),
+			)
+		})
+		.filter(|(_, sense)| *sense != WallSenseType::NONE)
+		.collect()
+}
+
+// Returns the position of the first blocking wall on the ray relative to the length of the ray,
+// or `None` if no wall blocks the ray
+fn cast_ray(
+	walls: &[(&WallBase, WallSenseType)],
+	origin: Point,
+	direction: Point,
+	length: f64,
+	angle: f64,
+) -> Option<f64> {
+	let target = Point::new(
+		origin.x + direction.x * length,
+		origin.y + direction.y * length,
+	);
+	let mut closest = f64::INFINITY;
+	// Limited walls only block if there is another limited wall in front of them
+	let mut closest_limited = f64::INFINITY;
+	for (wall, sense) in walls {
+		let (t, u) = match segment_intersection_parameters(origin, target, wall.p1, wall.p2) {
+			Some(parameters) => parameters,
+			None => continue,
+		};
+		// Written without negated comparisons so that NaN parameters never count as a hit
+		let hit = t > 0.0 && t <= 1.0 && (0.0..=1.0).contains(&u);
+		if !hit {
+			continue;
+		}
+		if let Some(see_through_angle) = wall.see_through_angle() {
+			if is_smaller_relative(angle, see_through_angle) {
+				continue;
+			}
+		}
+		if *sense == WallSenseType::LIMITED {
+			if t < closest_limited {
+				closest = closest.min(closest_limited);
+				closest_limited = t;
+			} else {
+				closest = closest.min(t);
+			}
+		} else {
+			closest = closest.min(t);
+		}
+	}
+	Some(closest).filter(|closest| closest.is_finite())
+}
@@ -1,10 +1,14 @@
 use crate::geometry::Point;
-use crate::raycasting::types::{
-	Cache, PolygonType, TileCache, TileId, VisionAngle, WallBase, WallHeight,
+use crate::raycasting::types::{Cache, PolygonType, TileCache, VisionAngle, WallBase, WallHeight};
+use crate::raycasting::{
+	compute_polygon, compute_polygon_extended, compute_polygon_with_fallback, compute_polygons,
+	find_collision, is_point_visible, sample_polygon, validate_polygons, Collision, DoorState,
+	DoorType, EdgeSource, ExtendedPolygon, Internals, PolygonSource, RaycastingError,
+	WallDirection, WallSenseType,
 };
-use crate::raycasting::{compute_polygon, DoorState, DoorType, WallDirection, WallSenseType};
-use js_sys::{Array, Object};
+use js_sys::{Array, Float64Array, Object};
 use rustc_hash::FxHashMap;
+use std::convert::TryFrom;
 use wasm_bindgen::prelude::*;
 
 #[allow(unused)]
@@ -27,9 +31,11 @@ pub fn js_compute_polygon(
 	rotation: f64,
 	polygon_type: &str,
 	internals_transfer: Option<InternalsTransfer>,
-) -> Object {
+	flat: Option<bool>,
+) -> Result<Object, JsValue> {
 	let origin = Point::from(&origin.into());
 	let polygon_type = PolygonType::from(polygon_type);
+	let mut internals = internals_transfer.as_ref().map(|_| Internals::default());
 	let (los, fov) = compute_polygon(
 		&cache,
 		origin,
@@ -39,24 +45,266 @@ pub fn js_compute_polygon(
 		density,
 		VisionAngle::from_rotation_and_angle(rotation, angle, origin),
 		polygon_type,
-		internals_transfer,
-	);
-	let result = Object::new();
+		internals.as_mut(),
+	)?;
+	if let (Some(internals_transfer), Some(internals)) = (internals_transfer, internals) {
+		internals_transfer
+			.set_endpoints(internals.endpoints.into_iter().map(JsValue::from).collect());
+	}
+	Ok(polygon_to_js(los, fov, flat.unwrap_or(false)))
+}
+
+// Each entry is either the polygon of the source or an object whose `error` describes why the source is invalid or its
+// polygon couldn't be computed. A failed source doesn't affect the others, so only that source needs to fall back.
+#[wasm_bindgen(js_name=computePolygons)]
+#[allow(dead_code)]
+pub fn js_compute_polygons(cache: &Cache, js_sources: Vec<JsValue>, flat: Option<bool>) -> Array {
+	let flat = flat.unwrap_or(false);
+	let sources = js_sources
+		.into_iter()
+		.map(|source| PolygonSource::try_from(&source.into()))
+		.collect::<Vec<_>>();
+	let valid_sources = sources
+		.iter()
+		.filter_map(|source| source.as_ref().ok().copied())
+		.collect::<Vec<_>>();
+	let mut results = compute_polygons(cache, &valid_sources).into_iter();
+	sources
+		.into_iter()
+		.map(
+			|source| match source.and_then(|_| results.next().unwrap()) {
+				Ok((los, fov)) => JsValue::from(polygon_to_js(los, fov, flat)),
+				Err(error) => {
+					let result = Object::new();
+					js_sys::Reflect::set(
+						&result,
+						&JsValue::from_str("error"),
+						&JsValue::from_str(&error.to_string()),
+					)
+					.unwrap();
+					result.into()
+				}
+			},
+		)
+		.collect()
+}
+
+// Only throws if the source is invalid. If the polygon couldn't be computed precisely, `fallback` is set, `error`
+// describes the failure and `call` contains the serialized call for bug reports.
+#[wasm_bindgen(js_name=computePolygonWithFallback)]
+#[allow(dead_code)]
+pub fn js_compute_polygon_with_fallback(
+	cache: &Cache,
+	source: JsValue,
+	flat: Option<bool>,
+) -> Result<Object, JsValue> {
+	let source = PolygonSource::try_from(&source.into())?;
+	let polygon = compute_polygon_with_fallback(cache, &source)?;
+	let fallback = polygon.used_fallback();
+	let result = polygon_to_js(polygon.los, polygon.fov, flat.unwrap_or(false));
 	js_sys::Reflect::set(
 		&result,
-		&JsValue::from_str("los"),
-		&los.into_iter().map(JsValue::from).collect::<Array>(),
+		&JsValue::from_str("fallback"),
+		&JsValue::from_bool(fallback),
 	)
 	.unwrap();
-	js_sys::Reflect::set(
-		&result,
-		&JsValue::from_str("fov"),
-		&fov.into_iter().map(JsValue::from).collect::<Array>(),
+	if let Some(error) = polygon.error {
+		js_sys::Reflect::set(
+			&result,
+			&JsValue::from_str("error"),
+			&JsValue::from_str(&error.to_string()),
+		)
+		.unwrap();
+	}
+	if let Some(call) = polygon.call {
+		js_sys::Reflect::set(
+			&result,
+			&JsValue::from_str("call"),
+			&JsValue::from_str(&call),
+		)
+		.unwrap();
+	}
+	Ok(result)
+}
+
+// Samples the polygons with rays, for sources for which `computePolygon` has already failed
+#[wasm_bindgen(js_name=samplePolygon)]
+#[allow(dead_code)]
+pub fn js_sample_polygon(
+	cache: &Cache,
+	source: JsValue,
+	flat: Option<bool>,
+) -> Result<Object, JsValue> {
+	let source = PolygonSource::try_from(&source.into())?;
+	let (los, fov) = sample_polygon(cache, &source)?;
+	Ok(polygon_to_js(los, fov, flat.unwrap_or(false)))
+}
+
+// Checks polygons in the flat format of `computePolygon` and returns a description of every defect that was found.
+// An empty array means that the polygons are valid.
+#[wasm_bindgen(js_name=validatePolygons)]
+#[allow(dead_code)]
+pub fn js_validate_polygons(source: JsValue, los: &[f64], fov: &[f64]) -> Result<Array, JsValue> {
+	let source = PolygonSource::try_from(&source.into())?;
+	Ok(validate_polygons(
+		source.origin,
+		source.radius,
+		source.distance,
+		source.density,
+		&flat_to_points(los),
+		&flat_to_points(fov),
 	)
-	.unwrap();
+	.iter()
+	.map(|defect| JsValue::from_str(&defect.to_string()))
+	.collect())
+}
+
+fn flat_to_points(coordinates: &[f64]) -> Vec<Point> {
+	coordinates
+		.chunks_exact(2)
+		.map(|point| Point::new(point[0], point[1]))
+		.collect()
+}
+
+#[wasm_bindgen(js_name=computePolygonExtended)]
+#[allow(dead_code, clippy::too_many_arguments)]
+pub fn js_compute_polygon_extended(
+	cache: &Cache,
+	origin: JsValue,
+	height: f64,
+	radius: f64,
+	distance: f64,
+	density: f64,
+	angle: f64,
+	rotation: f64,
+	polygon_type: &str,
+) -> Result<Object, JsValue> {
+	let origin = Point::from(&origin.into());
+	let polygon_type = PolygonType::from(polygon_type);
+	let (los, fov) = compute_polygon_extended(
+		&cache,
+		origin,
+		height,
+		radius,
+		distance,
+		density,
+		VisionAngle::from_rotation_and_angle(rotation, angle, origin),
+		polygon_type,
+		None,
+	)?;
+	let mut wall_ids = vec![None; cache.walls.len()];
+	for (id, &index) in &cache.wall_ids {
+		wall_ids[index] = Some(id.as_str());
+	}
+	let los_edges = edges_to_js(&los, &wall_ids);
+	let fov_edges = edges_to_js(&fov, &wall_ids);
+	let result = polygon_to_js(los.points, fov.points, false);
+	js_sys::Reflect::set(&result, &JsValue::from_str("losEdges"), &los_edges).unwrap();
+	js_sys::Reflect::set(&result, &JsValue::from_str("fovEdges"), &fov_edges).unwrap();
+	Ok(result)
+}
+
+// Each edge is reported as an object with a `type` ("wall", "arc", "wedge" or "shadow").
+// Wall edges additionally contain the id of the wall.
+fn edges_to_js(polygon: &ExtendedPolygon, wall_ids: &[Option<&str>]) -> Array {
+	polygon
+		.edges
+		.iter()
+		.map(|edge| {
+			let result = Object::new();
+			let edge_type = match edge {
+				EdgeSource::Wall(index) => {
+					let id = wall_ids[*index]
+						.map(JsValue::from_str)
+						.unwrap_or(JsValue::NULL);
+					js_sys::Reflect::set(&result, &JsValue::from_str("wall"), &id).unwrap();
+					"wall"
+				}
+				EdgeSource::Arc => "arc",
+				EdgeSource::Wedge => "wedge",
+				EdgeSource::Shadow => "shadow",
+			};
+			js_sys::Reflect::set(
+				&result,
+				&JsValue::from_str("type"),
+				&JsValue::from_str(edge_type),
+			)
+			.unwrap();
+			JsValue::from(result)
+		})
+		.collect()
+}
+
+// If `flat` is set, the polygons are returned as `Float64Array`s of the form `[x0, y0, x1, y1, ...]`,
+// which can be passed to PIXI without creating a wrapper object for each point
+fn polygon_to_js(los: Vec<Point>, fov: Vec<Point>, flat: bool) -> Object {
+	let result = Object::new();
+	js_sys::Reflect::set(&result, &JsValue::from_str("los"), &points_to_js(los, flat)).unwrap();
+	js_sys::Reflect::set(&result, &JsValue::from_str("fov"), &points_to_js(fov, flat)).unwrap();
 	result
 }
 
+fn points_to_js(points: Vec<Point>, flat: bool) -> JsValue {
+	if flat {
+		let coordinates = points
+			.iter()
+			.flat_map(|point| [point.x, point.y])
+			.collect::<Vec<_>>();
+		Float64Array::from(coordinates.as_slice()).into()
+	} else {
+		points
+			.into_iter()
+			.map(JsValue::from)
+			.collect::<Array>()
+			.into()
+	}
+}
+
+#[wasm_bindgen(js_name=isPointVisible)]
+#[allow(dead_code)]
+pub fn js_is_point_visible(
+	cache: &Cache,
+	origin: JsValue,
+	height: f64,
+	target: JsValue,
+	polygon_type: &str,
+) -> bool {
+	is_point_visible(
+		cache,
+		Point::from(&origin.into()),
+		height,
+		Point::from(&target.into()),
+		PolygonType::from(polygon_type),
+	)
+}
+
+// Returns the `point` at which the path is blocked and the id of the `wall` blocking it, or `undefined` if the path is free
+#[wasm_bindgen(js_name=checkCollision)]
+#[allow(dead_code)]
+pub fn js_check_collision(
+	cache: &Cache,
+	from: JsValue,
+	to: JsValue,
+	height: f64,
+) -> Option<Object> {
+	let Collision { point, wall } = find_collision(
+		cache,
+		Point::from(&from.into()),
+		Point::from(&to.into()),
+		height,
+	)?;
+	let id = cache
+		.wall_ids
+		.iter()
+		.find(|(_, &index)| index == wall)
+		.map(|(id, _)| JsValue::from_str(id))
+		.unwrap_or(JsValue::NULL);
+	let result = Object::new();
+	js_sys::Reflect::set(&result, &JsValue::from_str("point"), &JsValue::from(point)).unwrap();
+	js_sys::Reflect::set(&result, &JsValue::from_str("wall"), &id).unwrap();
+	Some(result)
+}
+
 #[allow(dead_code)]
 #[wasm_bindgen(js_name=updateOcclusion)]
 pub fn update_occlusion(cache: &mut Cache, js_tile_id: &str, occluded: bool) {
@@ -65,27 +313,56 @@ pub fn update_occlusion(cache: &mut Cache, js_tile_id: &str, occluded: bool) {
 	}
 }
 
+#[allow(dead_code)]
+#[wasm_bindgen(js_name=updateDoorState)]
+pub fn update_door_state(cache: &mut Cache, wall_id: &str, state: DoorState) -> bool {
+	cache.set_door_state(wall_id, state)
+}
+
 #[allow(dead_code)]
 #[wasm_bindgen(js_name=buildCache)]
 pub fn build_cache(js_walls: Vec<JsValue>, enable_height: bool) -> Cache {
-	let mut occluded = vec![];
-	let mut id_map = FxHashMap::default();
+	let (walls, wall_ids, tiles) = walls_from_js(js_walls, enable_height);
+	let mut cache = Cache::build(walls, tiles);
+	cache.wall_ids = wall_ids;
+	cache
+}
+
+pub(crate) fn walls_from_js(
+	js_walls: Vec<JsValue>,
+	enable_height: bool,
+) -> (Vec<WallBase>, FxHashMap<String, usize>, TileCache) {
+	let mut tiles = TileCache::default();
+	let mut wall_ids = FxHashMap::default();
 	let mut walls = Vec::with_capacity(js_walls.len());
 	for wall in js_walls {
 		let wall = JsWall::from(wall);
-		let roof = if let Some(roof) = wall.roof() {
-			let next_id = occluded.len();
-			let id = id_map.entry(roof.id()).or_insert_with(|| {
-				occluded.push(roof.occluded());
-				next_id
-			});
-			Some(*id)
-		} else {
-			None
-		};
-		walls.push(WallBase::from_js(&wall, roof, enable_height));
+		wall_ids.insert(wall.id(), walls.len());
+		walls.push(WallBase::from_js(&wall, &mut tiles, enable_height));
 	}
-	Cache::build(walls, TileCache { occluded, id_map })
+	(walls, wall_ids, tiles)
+}
+
+#[allow(dead_code)]
+#[wasm_bindgen(js_name=insertWall)]
+pub fn insert_wall(cache: &mut Cache, js_wall: JsValue, enable_height: bool) {
+	let wall = JsWall::from(js_wall);
+	let wall_base = WallBase::from_js(&wall, &mut cache.tiles, enable_height);
+	cache.insert_wall(wall.id(), wall_base);
+}
+
+#[allow(dead_code)]
+#[wasm_bindgen(js_name=replaceWall)]
+pub fn replace_wall(cache: &mut Cache, js_wall: JsValue, enable_height: bool) -> bool {
+	let wall = JsWall::from(js_wall);
+	let wall_base = WallBase::from_js(&wall, &mut cache.tiles, enable_height);
+	cache.replace_wall(&wall.id(), wall_base).is_some()
+}
+
+#[allow(dead_code)]
+#[wasm_bindgen(js_name=removeWall)]
+pub fn remove_wall(cache: &mut Cache, wall_id: &str) -> bool {
+	cache.remove_wall(wall_id).is_some()
 }
 
 #[allow(dead_code)]
@@ -94,6 +371,29 @@ pub fn wipe_cache(cache: Cache) {
 	drop(cache);
 }
 
+#[wasm_bindgen]
+extern "C" {
+	pub type JsPoint;
+
+	#[wasm_bindgen(method, getter)]
+	fn x(this: &JsPoint) -> f64;
+
+	#[wasm_bindgen(method, getter)]
+	fn y(this: &JsPoint) -> f64;
+}
+
+impl From<RaycastingError> for JsValue {
+	fn from(error: RaycastingError) -> Self {
+		js_sys::Error::new(&format!("Lichtgeschwindigkeit | {}", error)).into()
+	}
+}
+
+impl From<&JsPoint> for Point {
+	fn from(point: &JsPoint) -> Self {
+		Self::new(point.x(), point.y())
+	}
+}
+
 #[wasm_bindgen]
 extern "C" {
 	#[wasm_bindgen(js_namespace = console, js_name=warn)]
@@ -109,6 +409,9 @@ extern "C" {
 	pub type JsWallHeight;
 	pub type JsTile;
 
+	#[wasm_bindgen(method, getter)]
+	fn id(this: &JsWall) -> String;
+
 	#[wasm_bindgen(method, getter)]
 	fn data(this: &JsWall) -> JsWallData;
 
@@ -155,9 +458,68 @@ extern "C" {
 	fn bottom(this: &JsWallHeight) -> Option<f64>;
 }
 
+#[wasm_bindgen]
+extern "C" {
+	pub type JsPolygonSource;
+
+	#[wasm_bindgen(method, getter)]
+	fn origin(this: &JsPolygonSource) -> JsValue;
+
+	#[wasm_bindgen(method, getter)]
+	fn height(this: &JsPolygonSource) -> Option<f64>;
+
+	#[wasm_bindgen(method, getter)]
+	fn radius(this: &JsPolygonSource) -> Option<f64>;
+
+	#[wasm_bindgen(method, getter)]
+	fn distance(this: &JsPolygonSource) -> Option<f64>;
+
+	#[wasm_bindgen(method, getter)]
+	fn density(this: &JsPolygonSource) -> Option<f64>;
+
+	#[wasm_bindgen(method, getter)]
+	fn angle(this: &JsPolygonSource) -> Option<f64>;
+
+	#[wasm_bindgen(method, getter)]
+	fn rotation(this: &JsPolygonSource) -> Option<f64>;
+
+	#[wasm_bindgen(method, getter, js_name = "type")]
+	fn polygon_type(this: &JsPolygonSource) -> Option<String>;
+}
+
+// The radius and distance depend on the canvas, so there is no sensible default for them
+fn required(value: Option<f64>, property: &'static str) -> Result<f64, RaycastingError> {
+	value
+		.filter(|value| !value.is_nan())
+		.ok_or(RaycastingError::InvalidSource { property })
+}
+
+impl TryFrom<&JsPolygonSource> for PolygonSource {
+	type Error = RaycastingError;
+
+	fn try_from(source: &JsPolygonSource) -> Result<Self, RaycastingError> {
+		Ok(Self {
+			origin: Point::from(&source.origin().into()),
+			height: source.height().unwrap_or(0.0),
+			radius: required(source.radius(), "radius")?,
+			distance: required(source.distance(), "distance")?,
+			density: source.density().unwrap_or(6.0),
+			angle: source.angle().unwrap_or(360.0),
+			rotation: source.rotation().unwrap_or(0.0),
+			polygon_type: source
+				.polygon_type()
+				.map(|polygon_type| PolygonType::from(polygon_type.as_str()))
+				.unwrap_or(PolygonType::SIGHT),
+		})
+	}
+}
+
 impl WallBase {
-	pub fn from_js(wall: &JsWall, roof: Option<TileId>, enable_height: bool) -> Self {
+	pub fn from_js(wall: &JsWall, tiles: &mut TileCache, enable_height: bool) -> Self {
 		let data = wall.data();
+		let roof = wall
+			.roof()
+			.map(|roof| tiles.get_or_insert(roof.id(), roof.occluded()));
 		let c = data.c();
 		let height = if enable_height {
 			data.flags().wall_height().into()
@@ -1,13 +1,27 @@
-mod js_api;
+mod collision;
+mod error;
+mod fallback;
+#[cfg(feature = "wasm")]
+pub(crate) mod js_api;
 mod postprocessing;
 mod prepare;
 mod raycasting;
 mod types;
 mod util;
+mod validation;
+mod visibility;
 mod vision_angle;
+mod wall_grid;
 
-pub use raycasting::compute_polygon;
+pub use collision::{find_collision, Collision};
+pub use error::RaycastingError;
+pub use fallback::{compute_polygon_with_fallback, sample_polygon, FallbackPolygon};
+pub use raycasting::{compute_polygon, compute_polygon_extended, compute_polygons, PolygonResult};
 pub use types::{
-	Cache, DoorState, DoorType, PolygonType, TileCache, VisionAngle, WallBase, WallDirection,
-	WallHeight, WallSenseType,
+	Cache, DoorState, DoorType, EdgeSource, ExposedEndpoint, ExtendedPolygon, Internals,
+	Intersection, PhaseTimings, PolygonSource, PolygonType, TileCache, TileId, VisionAngle,
+	WallBase, WallDirection, WallHeight, WallSenseType,
 };
+pub use validation::{validate_polygons, PolygonDefect, PolygonKind};
+pub use visibility::is_point_visible;
+pub use wall_grid::WallGrid;
@@ -1,16 +1,15 @@
 use crate::geometry::{Circle, Line, Point};
-use crate::raycasting::types::FovPoint;
+use crate::raycasting::error::RaycastingError;
+use crate::raycasting::types::{EdgeSource, ExtendedPolygon, FovPoint};
 use std::f64::consts::PI;
 use std::mem::swap;
 
-use super::util::is_intersection_on_segment;
-
 pub fn calculate_fov(
 	origin: Point,
 	radius: f64,
 	los_points: &Vec<FovPoint>,
 	start_gap_fov: bool,
-) -> Vec<FovPoint> {
+) -> Result<Vec<FovPoint>, RaycastingError> {
 	let fov = Circle {
 		center: origin,
 		radius,
@@ -38,6 +37,7 @@ pub fn calculate_fov(
 							point,
 							angle: los_point.angle,
 							gap: false,
+							edge: previous_los.edge,
 						});
 					} else {
 						let line = Line::from_points(previous_los.point, los_point.point);
@@ -55,6 +55,7 @@ pub fn calculate_fov(
 								point: relevant_intersection.point,
 								angle: relevant_intersection.angle,
 								gap: false,
+								edge: previous_los.edge,
 							});
 						}
 					}
@@ -63,9 +64,14 @@ pub fn calculate_fov(
 
 				if i == los_points.len() - 1 {
 					if start_gap_fov && !los_point.gap {
-						let line =
-							Line::from_points(los_point.point, los_points.first().unwrap().point);
-						let intersections = fov.intersections(&line).unwrap();
+						let next_los = los_points.first().unwrap();
+						let line = Line::from_points(los_point.point, next_los.point);
+						let intersections = fov.intersections(&line).ok_or(
+							RaycastingError::MissingCircleIntersection {
+								p1: los_point.point,
+								p2: next_los.point,
+							},
+						)?;
 						let exit_intersection;
 						if intersections.0.angle > intersections.1.angle {
 							exit_intersection = intersections.0;
@@ -76,6 +82,7 @@ pub fn calculate_fov(
 							point: exit_intersection.point,
 							angle: exit_intersection.angle,
 							gap: false,
+							edge: los_point.edge,
 						});
 					}
 				}
@@ -98,6 +105,7 @@ pub fn calculate_fov(
 							point,
 							angle: los_point.angle,
 							gap: true,
+							edge: EdgeSource::Arc,
 						});
 					} else {
 						let line = Line::from_points(point_before_hidden.point, hidden_point.point);
@@ -115,12 +123,18 @@ pub fn calculate_fov(
 								point: relevant_intersection.point,
 								angle: relevant_intersection.angle,
 								gap: true,
+								edge: EdgeSource::Arc,
 							});
 						}
 						if !start_gap_fov && i == los_points.len() - 1 {
 							let next_los = los_points.first().unwrap();
 							let line = Line::from_points(los_point.point, next_los.point);
-							let intersections = fov.intersections(&line).unwrap();
+							let intersections = fov.intersections(&line).ok_or(
+								RaycastingError::MissingCircleIntersection {
+									p1: los_point.point,
+									p2: next_los.point,
+								},
+							)?;
 							let entry;
 							// The wall is to the right of the token, so the angles are inverted
 							if intersections.0.angle > intersections.1.angle {
@@ -132,13 +146,19 @@ pub fn calculate_fov(
 								point: entry.point,
 								angle: entry.angle,
 								gap: false,
+								edge: los_point.edge,
 							});
 						}
 					}
 				} else {
 					let previous_los = los_points.last().unwrap();
 					let line = Line::from_points(previous_los.point, los_point.point);
-					let intersections = fov.intersections(&line).unwrap();
+					let intersections = fov.intersections(&line).ok_or(
+						RaycastingError::MissingCircleIntersection {
+							p1: previous_los.point,
+							p2: los_point.point,
+						},
+					)?;
 					let exit;
 					// The wall is to the right of the token, so the angles are inverted
 					if intersections.0.angle > intersections.1.angle {
@@ -150,6 +170,7 @@ pub fn calculate_fov(
 						point: exit.point,
 						angle: exit.angle,
 						gap: true,
+						edge: EdgeSource::Arc,
 					});
 				}
 			} else {
@@ -178,11 +199,13 @@ pub fn calculate_fov(
 									point: entry.point,
 									angle: entry.angle,
 									gap: false,
+									edge: previous_los.edge,
 								});
 								fov_points.push(FovPoint {
 									point: exit.point,
 									angle: exit.angle,
 									gap: true,
+									edge: EdgeSource::Arc,
 								});
 							}
 						}
@@ -207,16 +230,12 @@ pub fn calculate_fov(
 								swap(&mut entry, &mut exit);
 								overflow = true;
 							}
-							if is_intersection_on_segment(
-								entry.point,
-								line,
-								los_point.point,
-								next_los.point,
-							) {
+							if entry.position > 0.0 && entry.position < 1.0 {
 								let fov_point = FovPoint {
 									point: entry.point,
 									angle: entry.angle,
 									gap: false,
+									edge: los_point.edge,
 								};
 								if overflow {
 									fov_points.push(fov_point);
@@ -225,16 +244,12 @@ pub fn calculate_fov(
 									fov_points.insert(0, fov_point);
 								}
 							}
-							if is_intersection_on_segment(
-								exit.point,
-								line,
-								los_point.point,
-								next_los.point,
-							) {
+							if exit.position > 0.0 && exit.position < 1.0 {
 								fov_points.push(FovPoint {
 									point: exit.point,
 									angle: exit.angle,
 									gap: true,
+									edge: EdgeSource::Arc,
 								});
 							}
 						}
@@ -243,7 +258,7 @@ pub fn calculate_fov(
 			}
 		}
 	}
-	fov_points
+	Ok(fov_points)
 }
 
 pub fn fill_gaps(
@@ -252,8 +267,18 @@ pub fn fill_gaps(
 	origin: Point,
 	radius: f64,
 	radial_density: f64,
-) -> Vec<Point> {
+) -> ExtendedPolygon {
 	let mut output = Vec::new();
+	// The source of the edge that leads to the point with the same index in `output`
+	let mut incoming_edges = Vec::new();
+	// Edges on a ray from the origin are sides of the vision cone if they start at the origin
+	let radial_edge = |point: Point| {
+		if point == origin {
+			EdgeSource::Wedge
+		} else {
+			EdgeSource::Shadow
+		}
+	};
 
 	if points.len() == 0 {
 		let mut a = -PI;
@@ -262,6 +287,7 @@ pub fn fill_gaps(
 				origin.x - (a.cos() * radius),
 				origin.y - (a.sin() * radius),
 			));
+			incoming_edges.push(EdgeSource::Arc);
 			a += radial_density;
 		}
 	} else {
@@ -275,7 +301,8 @@ pub fn fill_gaps(
 			let current = current.first().unwrap();
 			let previous;
 			if i == 0 {
-				previous = upper.last().unwrap();
+				// With only a single point, the point is its own predecessor
+				previous = upper.last().unwrap_or(current);
 			} else {
 				previous = lower.last().unwrap();
 			}
@@ -289,6 +316,7 @@ pub fn fill_gaps(
 					Point::new(origin.x - (a.cos() * radius), origin.y - (a.sin() * radius));
 				if !first_filler.is_same_as(&previous.point) {
 					output.push(first_filler);
+					incoming_edges.push(radial_edge(previous.point));
 				}
 				a += radial_density;
 				while a < current.angle {
@@ -296,6 +324,7 @@ pub fn fill_gaps(
 						origin.x - (a.cos() * radius),
 						origin.y - (a.sin() * radius),
 					));
+					incoming_edges.push(EdgeSource::Arc);
 					a += radial_density;
 				}
 				let last_filler = Point::new(
@@ -304,11 +333,22 @@ pub fn fill_gaps(
 				);
 				if !last_filler.is_same_as(&current.point) {
 					output.push(last_filler);
+					incoming_edges.push(EdgeSource::Arc);
+					incoming_edges.push(radial_edge(current.point));
+				} else {
+					incoming_edges.push(EdgeSource::Arc);
 				}
+			} else {
+				incoming_edges.push(previous.edge);
 			}
 			output.push(current.point);
 		}
 	}
 
-	output
+	// Every point is the start of the edge that leads into the following point
+	incoming_edges.rotate_left(1);
+	ExtendedPolygon {
+		points: output,
+		edges: incoming_edges,
+	}
 }
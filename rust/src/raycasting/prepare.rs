@@ -1,51 +1,83 @@
-use crate::geometry::Point;
+use crate::geometry::{orient2d, Point};
 use crate::ptr_indexed_hash_set::PtrIndexedHashSet;
-use crate::raycasting::types::{Cache, Endpoint, VisionAngle, Wall};
+use crate::raycasting::error::RaycastingError;
+use crate::raycasting::types::{Cache, Endpoint, VisionAngle, Wall, WallWithAngles};
 use crate::raycasting::vision_angle::restrict_vision_angle;
-use crate::raycasting::{DoorState, DoorType, PolygonType, WallSenseType};
+use crate::raycasting::{PolygonType, WallSenseType};
 use rustc_hash::FxHashMap;
 use std::cell::RefCell;
 use std::f64::consts::PI;
 use std::mem::swap;
 use std::rc::Rc;
 
+/// Allocations that can be reused between multiple calls of `prepare_data`
+#[derive(Default)]
+pub struct PrepareBuffers {
+	candidates: Vec<usize>,
+	relevant_walls: Vec<bool>,
+	endpoints: FxHashMap<Point, Rc<RefCell<Endpoint>>>,
+	restricted_walls: Vec<WallWithAngles>,
+}
+
+/// The endpoints sorted by their angle, the walls that are hit by a ray with the angle -π
+/// and whether walls outside of the relevance radius have been skipped
+type PreparedData = (Vec<Rc<RefCell<Endpoint>>>, PtrIndexedHashSet<Wall>, bool);
+
 pub fn prepare_data(
+	buffers: &mut PrepareBuffers,
 	cache: &Cache,
 	origin: Point,
 	height: f64,
+	relevance_radius: Option<f64>,
 	vision_angle: &Option<VisionAngle>,
 	polygon_type: PolygonType,
-) -> (Vec<Rc<RefCell<Endpoint>>>, PtrIndexedHashSet<Wall>) {
-	let wall_bases = &cache.walls;
+) -> Result<PreparedData, RaycastingError> {
+	let PrepareBuffers {
+		candidates,
+		relevant_walls,
+		endpoints,
+		restricted_walls,
+	} = buffers;
+
+	// Walls that are completely outside of the relevance radius are skipped. This is only exact if the LOS turns out
+	// to be enclosed by the remaining walls, which the caller has to check.
+	let relevance_radius = relevance_radius.filter(|&relevance_radius| {
+		cache
+			.grid
+			.circle_candidates(origin, relevance_radius, candidates)
+	});
+	let culled = relevance_radius.is_some();
+	if let Some(relevance_radius) = relevance_radius {
+		relevant_walls.clear();
+		relevant_walls.resize(cache.walls.len(), false);
+		// Allow for a bit of float imprecision when calculating the distance
+		let max_distance = relevance_radius * 1.000001;
+		candidates.retain(|&index| {
+			let wall = &cache.walls[index];
+			origin.distance_to_segment(&wall.p1, &wall.p2) <= max_distance
+		});
+		for &index in candidates.iter() {
+			relevant_walls[index] = true;
+		}
+	} else {
+		candidates.clear();
+		candidates.extend(0..cache.walls.len());
+	}
+
 	// TODO Cell/RefCell introduces runtime overhead
-	let mut endpoints = FxHashMap::default();
 	let mut start_walls = PtrIndexedHashSet::new();
-	let mut walls = Vec::with_capacity(wall_bases.len());
-	let mut restricted_walls = Vec::new();
 
-	for wall in wall_bases {
+	for &index in candidates.iter() {
+		let wall = &cache.walls[index];
 		if wall.p1 == wall.p2 {
 			continue;
 		}
-		if wall.p1 == origin || wall.p2 == origin {
-			continue;
-		}
-		if (wall.line.is_vertical() && wall.p1.x == origin.x)
-			|| (wall.line.is_horizontal() && wall.line.p1.y == origin.y)
-		{
+		// Walls on a line through the origin don't have any width and don't influence the polygon
+		if orient2d(wall.p1, wall.p2, origin) == 0.0 {
 			continue;
 		}
 
-		if wall.door != DoorType::NONE && wall.ds == DoorState::OPEN {
-			continue;
-		}
-
-		let sense = wall.current_sense(&cache, polygon_type);
-		if sense == WallSenseType::NONE {
-			continue;
-		}
-
-		if wall.height.bottom > height || wall.height.top < height {
+		if wall.effective_sense(cache, height, polygon_type) == WallSenseType::NONE {
 			continue;
 		}
 
@@ -56,8 +88,8 @@ pub fn prepare_data(
 			.remove(&wall.p2)
 			.unwrap_or_else(|| Rc::new(RefCell::new(Endpoint::new(origin, wall.p2))));
 
-		// Check if the wall's line goes through the light sources center.
-		// If so, the wall doesn't have any width and doesn't influence light calculation
+		// The angles of walls that almost go through the origin may still be the same after rounding.
+		// The sweep can't handle walls that start and end at the same angle, so those walls are skipped as well.
 		if e1.borrow().angle == e2.borrow().angle
 			|| (e1.borrow().angle - e2.borrow().angle).abs() == PI
 		{
@@ -84,19 +116,32 @@ pub fn prepare_data(
 		}
 
 		let wall = Rc::new(Wall::from_base(
+			index,
 			*wall,
 			Rc::clone(&end),
 			&cache,
 			polygon_type,
 		));
-		if let Some(split_walls) = restrict_vision_angle(&wall, &start, &end, &vision_angle) {
+		let split_walls = match restrict_vision_angle(&wall, &start, &end, &vision_angle) {
+			Ok(split_walls) => split_walls,
+			Err(error) => {
+				discard_endpoints(
+					endpoints
+						.drain()
+						.map(|(_, endpoint)| endpoint)
+						.chain([start, end]),
+				);
+				restricted_walls.clear();
+				return Err(error);
+			}
+		};
+		if let Some(split_walls) = split_walls {
 			for wall in &split_walls {
 				if let Some(wall) = wall {
 					restricted_walls.push(*wall);
 				}
 			}
 		} else {
-			walls.push(Rc::clone(&wall));
 			start.borrow_mut().starting_walls.push(Rc::clone(&wall));
 			end.borrow_mut().ending_walls.push(Rc::clone(&wall));
 
@@ -111,7 +156,7 @@ pub fn prepare_data(
 		endpoints.insert(end_point, end);
 	}
 
-	for wall in restricted_walls {
+	for wall in restricted_walls.drain(..) {
 		let e1 = endpoints.remove(&wall.p1).unwrap_or_else(|| {
 			Rc::new(RefCell::new(Endpoint::new_with_precomputed_angle(
 				wall.p1,
@@ -143,7 +188,6 @@ pub fn prepare_data(
 		}
 
 		let wall = Rc::new(wall.to_wall(Rc::clone(&end)));
-		walls.push(Rc::clone(&wall));
 		start.borrow_mut().starting_walls.push(Rc::clone(&wall));
 		end.borrow_mut().ending_walls.push(Rc::clone(&wall));
 
@@ -158,25 +202,54 @@ pub fn prepare_data(
 	}
 
 	for intersection in &cache.intersections {
+		if culled && !(relevant_walls[intersection.walls.0] && relevant_walls[intersection.walls.1])
+		{
+			continue;
+		}
 		if height >= intersection.height.bottom && height <= intersection.height.top {
 			endpoints
 				.entry(intersection.point)
 				.or_insert_with(|| Rc::new(RefCell::new(Endpoint::new(origin, intersection.point))))
 				.borrow_mut()
-				.is_intersection = true;
+				.crossing_walls
+				.extend([intersection.walls.0, intersection.walls.1]);
 		}
 	}
 
 	let mut sorted_endpoints = endpoints
-		.values()
+		.drain()
+		.map(|(_, val)| val)
 		.filter(|val| {
-			val.borrow().is_intersection
+			val.borrow().is_intersection()
 				|| val.borrow().starting_walls.len() + val.borrow().ending_walls.len() > 0
 		})
-		.map(|val| Rc::clone(&val))
 		.collect::<Vec<_>>();
-	sorted_endpoints
-		.sort_unstable_by(|e1, e2| e1.borrow().angle.partial_cmp(&e2.borrow().angle).unwrap());
+	if let Some(endpoint) = sorted_endpoints
+		.iter()
+		.find(|endpoint| endpoint.borrow().angle.is_nan())
+	{
+		let point = endpoint.borrow().point;
+		discard_endpoints(sorted_endpoints);
+		return Err(RaycastingError::InvalidAngle { point });
+	}
+	// Endpoints with the same angle are ordered by their position, so the result doesn't depend on the order of the hash map
+	sorted_endpoints.sort_unstable_by(|e1, e2| {
+		let e1 = e1.borrow();
+		let e2 = e2.borrow();
+		e1.angle
+			.partial_cmp(&e2.angle)
+			.unwrap()
+			.then(e1.point.x.partial_cmp(&e2.point.x).unwrap())
+			.then(e1.point.y.partial_cmp(&e2.point.y).unwrap())
+	});
+
+	Ok((sorted_endpoints, start_walls, culled))
+}
 
-	(sorted_endpoints, start_walls)
+// Walls and endpoints have Rc's to each other in a cyclic way. Endpoints that are dropped early because of an error need to be cleaned up to avoid a memory leak.
+fn discard_endpoints<I: IntoIterator<Item = Rc<RefCell<Endpoint>>>>(endpoints: I) {
+	for endpoint in endpoints {
+		endpoint.borrow_mut().starting_walls.clear();
+		endpoint.borrow_mut().ending_walls.clear();
+	}
 }
@@ -3,15 +3,20 @@ use crate::geometry::*;
 use std::cell::RefCell;
 use std::mem::swap;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 // TODO Try out if this is acutally the optimal hasher to use
 use crate::ptr_indexed_hash_set::PtrIndexedHashSet;
-use crate::raycasting::js_api::InternalsTransfer;
+use crate::raycasting::error::RaycastingError;
 use crate::raycasting::postprocessing::{calculate_fov, fill_gaps};
-use crate::raycasting::prepare::prepare_data;
+use crate::raycasting::prepare::{prepare_data, PrepareBuffers};
 use crate::raycasting::types::*;
-use crate::raycasting::util::{is_intersection_on_wall, is_smaller_relative};
+use crate::raycasting::util::{is_smaller_relative, walls_collinear, walls_cross, LineSegment};
+use crate::raycasting::validation::validate_polygons;
 use crate::raycasting::vision_angle::add_vision_wedge;
 
+/// The LOS and the FOV polygon of a source
+pub type PolygonResult = Result<(Vec<Point>, Vec<Point>), RaycastingError>;
+
 pub fn compute_polygon(
 	cache: &Cache,
 	origin: Point,
@@ -21,35 +26,154 @@ pub fn compute_polygon(
 	density: f64,
 	vision_angle: Option<VisionAngle>,
 	polygon_type: PolygonType,
-	internals_transfer: Option<InternalsTransfer>,
-) -> (Vec<Point>, Vec<Point>) {
-	let (endpoints, mut start_walls) =
-		prepare_data(cache, origin, height, &vision_angle, polygon_type);
+	internals: Option<&mut Internals>,
+) -> PolygonResult {
+	let (los, fov) = compute_polygon_extended(
+		cache,
+		origin,
+		height,
+		radius,
+		distance,
+		density,
+		vision_angle,
+		polygon_type,
+		internals,
+	)?;
+	Ok((los.points, fov.points))
+}
 
-	let (mut los_points, start_gap_los, mut start_gap_fov) =
-		calculate_los(origin, radius, &endpoints, &mut start_walls);
+/// Like `compute_polygon`, but additionally reports what bounds each edge of the polygons
+#[allow(clippy::too_many_arguments)]
+pub fn compute_polygon_extended(
+	cache: &Cache,
+	origin: Point,
+	height: f64,
+	radius: f64,
+	distance: f64,
+	density: f64,
+	vision_angle: Option<VisionAngle>,
+	polygon_type: PolygonType,
+	internals: Option<&mut Internals>,
+) -> Result<(ExtendedPolygon, ExtendedPolygon), RaycastingError> {
+	compute_polygon_with_buffers(
+		&mut PrepareBuffers::default(),
+		cache,
+		origin,
+		height,
+		radius,
+		distance,
+		density,
+		vision_angle,
+		polygon_type,
+		internals,
+	)
+}
 
-	if let Some(vision_angle) = vision_angle {
-		los_points = add_vision_wedge(los_points, origin, vision_angle, &mut start_gap_fov);
+/// Computes the polygons of all sources. Allocations are shared between the sources,
+/// which makes this faster than calling `compute_polygon` for each source.
+pub fn compute_polygons(cache: &Cache, sources: &[PolygonSource]) -> Vec<PolygonResult> {
+	let mut buffers = PrepareBuffers::default();
+	sources
+		.iter()
+		.map(|source| {
+			let (los, fov) = compute_polygon_with_buffers(
+				&mut buffers,
+				cache,
+				source.origin,
+				source.height,
+				source.radius,
+				source.distance,
+				source.density,
+				VisionAngle::from_rotation_and_angle(source.rotation, source.angle, source.origin),
+				source.polygon_type,
+				None,
+			)?;
+			Ok((los.points, fov.points))
+		})
+		.collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn compute_polygon_with_buffers(
+	buffers: &mut PrepareBuffers,
+	cache: &Cache,
+	origin: Point,
+	height: f64,
+	radius: f64,
+	distance: f64,
+	density: f64,
+	vision_angle: Option<VisionAngle>,
+	polygon_type: PolygonType,
+	mut internals: Option<&mut Internals>,
+) -> Result<(ExtendedPolygon, ExtendedPolygon), RaycastingError> {
+	if !origin.x.is_finite() || !origin.y.is_finite() {
+		return Err(RaycastingError::InvalidOrigin { origin });
 	}
+	// The gaps are filled in steps of the density, which would never finish otherwise
+	if !density.is_finite() || density <= 0.0 {
+		return Err(RaycastingError::InvalidDensity { density });
+	}
+
+	let mut timings = PhaseTimings::default();
+	let measure = internals
+		.as_ref()
+		.is_some_and(|internals| internals.timings.is_some());
+	let mut start = measure.then(Instant::now);
+	let validate = cfg!(debug_assertions)
+		|| internals
+			.as_ref()
+			.is_some_and(|internals| internals.validate);
+
+	// Walls outside of the radius usually don't matter, so they are skipped at first. If the LOS isn't enclosed within
+	// the radius by the remaining walls, walls further away might still shape it and all walls need to be considered.
+	// Hidden walls that cross a side of a vision cone add endpoints on that side, so skipping them may change the
+	// rounding of the LOS points there.
+	let mut cull = true;
+	let (mut los_points, start_gap_los, start_gap_fov) = loop {
+		let (endpoints, mut start_walls, culled) = prepare_data(
+			buffers,
+			cache,
+			origin,
+			height,
+			cull.then_some(radius),
+			&vision_angle,
+			polygon_type,
+		)?;
 
-	let mut fov_points = calculate_fov(origin, radius, &los_points, start_gap_fov);
+		start = lap(start, &mut timings.prepare_data);
 
-	// Report endpoints if debugging is enabled
-	if let Some(internals_transfer) = internals_transfer {
-		internals_transfer.set_endpoints(
-			endpoints
+		let los_result = calculate_los(origin, radius, &endpoints, &mut start_walls);
+
+		// Report endpoints if debugging is enabled
+		if let Some(internals) = internals.as_deref_mut() {
+			internals.endpoints = endpoints
 				.iter()
-				.map(|endpoint| ExposedEndpoint::from(&*endpoint.borrow()).into())
-				.collect(),
-		);
-	}
+				.map(|endpoint| ExposedEndpoint::from(&*endpoint.borrow()))
+				.collect();
+		}
 
-	// Clean up references to the walls in the endpoints to avoid a memory leak (walls and endpoints have Rc's to each other in a cyclic way
-	for endpoint in endpoints {
-		endpoint.borrow_mut().starting_walls.clear();
-		endpoint.borrow_mut().ending_walls.clear();
-	}
+		// Clean up references to the walls in the endpoints to avoid a memory leak (walls and endpoints have Rc's to each other in a cyclic way
+		for endpoint in endpoints {
+			endpoint.borrow_mut().starting_walls.clear();
+			endpoint.borrow_mut().ending_walls.clear();
+		}
+
+		let (mut los_points, start_gap_los, mut start_gap_fov) = los_result?;
+
+		if let Some(vision_angle) = &vision_angle {
+			los_points = add_vision_wedge(los_points, origin, vision_angle, &mut start_gap_fov);
+		}
+
+		start = lap(start, &mut timings.calculate_los);
+		if culled && !is_enclosed(origin, radius, &los_points, start_gap_los) {
+			cull = false;
+			continue;
+		}
+		break (los_points, start_gap_los, start_gap_fov);
+	};
+
+	let mut fov_points = calculate_fov(origin, radius, &los_points, start_gap_fov)?;
+	let start = lap(start, &mut timings.calculate_fov);
 
 	let radial_density = density.to_radians();
 	let los = fill_gaps(
@@ -66,8 +190,48 @@ pub fn compute_polygon(
 		radius,
 		radial_density,
 	);
+	lap(start, &mut timings.fill_gaps);
+	if let Some(total) = internals.and_then(|internals| internals.timings.as_mut()) {
+		*total += timings;
+	}
+
+	if validate {
+		let defects =
+			validate_polygons(origin, radius, distance, density, &los.points, &fov.points);
+		if let Some(&defect) = defects.first() {
+			return Err(RaycastingError::InvalidPolygon { defect });
+		}
+	}
+
+	Ok((los, fov))
+}
+
+// Whether every ray from the origin hits a wall within the radius. Walls outside of the radius can't change the LOS then.
+fn is_enclosed(origin: Point, radius: f64, los_points: &[FovPoint], start_gap_los: bool) -> bool {
+	let (last, others) = match los_points.split_last() {
+		Some(points) => points,
+		None => return false,
+	};
+	// `fill_gaps` replaces the gap of the last point by the gap at the angle -π
+	let last_gap = if last.point == origin {
+		last.gap
+	} else {
+		start_gap_los
+	};
+	!last_gap
+		&& others.iter().all(|point| !point.gap)
+		&& los_points
+			.iter()
+			.all(|point| origin.distance_to(&point.point) <= radius)
+}
 
-	(los, fov)
+// Adds the time since `start` to `duration` and starts the next phase
+fn lap(start: Option<Instant>, duration: &mut Duration) -> Option<Instant> {
+	start.map(|start| {
+		let now = Instant::now();
+		*duration += now - start;
+		now
+	})
 }
 
 fn calculate_los(
@@ -75,23 +239,23 @@ fn calculate_los(
 	radius: f64,
 	endpoints: &Vec<Rc<RefCell<Endpoint>>>,
 	start_walls: &mut PtrIndexedHashSet<Wall>,
-) -> (Vec<FovPoint>, bool, bool) {
+) -> Result<(Vec<FovPoint>, bool, bool), RaycastingError> {
 	let mut los_points = Vec::new();
 	let current_walls = start_walls;
-	let mut current_ray_line = Line::new(0.0, origin.y as f64, origin);
+	let mut current_ray_line = Line::horizontal(origin);
 	let mut closest_los_wall =
-		find_closest_wall::<_, false>(origin, &current_ray_line, &*current_walls);
+		find_closest_wall::<_, false>(origin, &current_ray_line, None, &*current_walls)?;
 	let start_gap_los = closest_los_wall.is_none();
 	let start_gap_fov = closest_los_wall
 		.as_ref()
-		.filter(|closest_wall| closest_wall.distance < radius)
+		.filter(|closest_wall| closest_wall.distance_squared < radius * radius)
 		.is_none();
 
 	for i in 0..endpoints.len() {
 		let endpoint = endpoints[i].borrow();
 		let old_los_wall = closest_los_wall.clone();
 		current_ray_line = Line::from_points(origin, endpoint.point);
-		let mut closest_wall_could_change = endpoint.is_intersection;
+		let mut closest_wall_could_change = endpoint.is_intersection();
 		for wall in &endpoint.ending_walls {
 			let element_removed = current_walls.remove(wall);
 			if element_removed {
@@ -111,33 +275,14 @@ fn calculate_los(
 						wall.end.borrow().angle,
 						closest_wall.wall.end.borrow().angle,
 					) {
-						// Probe if the walls have any chance of intersecting. If not, the new wall is either completely in front or behind of the currently closest wall.
-						// TODO Check if the heuristic if faster. If not, adjust the above comment
-						// TODO Only do segment intersection
-						let mut intersection = wall.line.intersection(&closest_wall.wall.line);
-						// The above only gets the intersection point between the lines. We also need to check if the point is on both wall segments
-						if let Some(i) = intersection {
-							if !is_intersection_on_wall(i, wall)
-								|| !is_intersection_on_wall(i, &closest_wall.wall)
-							{
-								intersection = None;
-							}
-						}
-						if intersection.is_none() {
-							// Check if the endpoint is before or behind the currently closest wall - if it is behind the wall is completely covered, skip it.
-							let intersection = current_ray_line
-								.intersection(&closest_wall.wall.line)
-								.unwrap();
-
-							// For optimization purposes we use Math.pow instead of Math.hypot, because that way we save ourselfs of doing an expensive Math.sqrt, whcih wouldn't change the result of the comparison anyway
-							let endpoint_distance = (origin.x - endpoint.point.x).powi(2)
-								+ (origin.y - endpoint.point.y).powi(2);
-							let intersection_distance = (origin.x - intersection.x).powi(2)
-								+ (origin.y - intersection.y).powi(2);
-
-							// TODO Cull T-Intersections that are oriented away from the current wall
-							// endpoint_distance > intersection_distance while respecting float inprecisions
-							if endpoint_distance - intersection_distance > 0.00005 {
+						// If the walls don't cross, the new wall is either completely in front or behind of the currently closest wall.
+						if !walls_cross(wall, &closest_wall.wall) {
+							// If the endpoint is on the other side of the closest wall than the origin, the new wall is completely covered. Skip it.
+							let closest_line = closest_wall.wall.line();
+							if is_opposite_side(
+								closest_line.side_of(origin),
+								closest_line.side_of(endpoint.point),
+							) {
 								continue;
 							}
 						}
@@ -157,26 +302,28 @@ fn calculate_los(
 		}
 
 		if closest_wall_could_change {
-			closest_los_wall =
-				find_closest_wall::<_, false>(origin, &current_ray_line, &*current_walls);
+			closest_los_wall = find_closest_wall::<_, false>(
+				origin,
+				&current_ray_line,
+				Some(&endpoint),
+				&*current_walls,
+			)?;
 		}
 
 		if old_los_wall != closest_los_wall {
 			if let Some(old_closest_wall) = old_los_wall {
 				if let Some(intersection) =
-					current_ray_line.intersection(&old_closest_wall.wall.line)
+					endpoint.ray_intersection(&current_ray_line, &old_closest_wall.wall)
 				{
 					if closest_los_wall.is_none()
-						|| !closest_los_wall
-							.as_ref()
-							.unwrap()
-							.intersection
-							.is_same_as(&intersection)
+						|| closest_los_wall.as_ref().unwrap().intersection != intersection
 					{
+						// The edge to the intersection with the new closest wall lies on the current ray
 						los_points.push(FovPoint {
 							point: intersection,
 							angle: endpoint.angle,
 							gap: false,
+							edge: EdgeSource::Shadow,
 						});
 					}
 				}
@@ -187,70 +334,79 @@ fn calculate_los(
 					point: closest_wall.intersection,
 					angle: endpoint.angle,
 					gap: false,
+					edge: EdgeSource::Wall(closest_wall.wall.index),
 				});
 			} else {
-				los_points.last_mut().unwrap().gap = true;
+				los_points
+					.last_mut()
+					.ok_or(RaycastingError::MissingLosPoint {
+						angle: endpoint.angle,
+					})?
+					.gap = true;
 			}
 		}
 	}
 
-	(los_points, start_gap_los, start_gap_fov)
+	Ok((los_points, start_gap_los, start_gap_fov))
 }
 
+// `endpoint` is the endpoint the ray goes through, if there is one.
+// Walls that hit the ray at exactly the same point are tied. Rounding may put the computed intersections of collinear walls
+// at slightly different points, so those are tied as well. Ties are broken by looking a little further counter clockwise.
 fn find_closest_wall<'a, I, const IS_TIEBREAKER: bool>(
 	origin: Point,
 	current_ray_line: &Line,
+	endpoint: Option<&Endpoint>,
 	current_walls: I,
-) -> Option<ClosestWall>
+) -> Result<Option<ClosestWall>, RaycastingError>
 where
 	I: IntoIterator<Item = &'a Rc<Wall>>,
 {
-	let mut closest_wall = None;
+	let mut closest_wall: Option<ClosestWall> = None;
 	let mut second_closest_wall = None;
-	let use_y_distance = current_ray_line.is_vertical() || current_ray_line.m.abs() > 1.0;
 	let mut ties = Vec::new();
 	let mut second_closest_ties = Vec::new();
 	for wall in current_walls {
-		let intersection = current_ray_line.intersection(&wall.line);
+		let intersection = match endpoint {
+			Some(endpoint) => endpoint.ray_intersection(current_ray_line, wall),
+			None => current_ray_line.intersection(&wall.line()),
+		};
 		if let Some(intersection) = intersection {
-			let distance;
-			if use_y_distance {
-				distance = (intersection.y - origin.y).abs();
-			} else {
-				distance = (intersection.x - origin.x).abs();
-			}
-			if let Some(closest_distance) = closest_wall
-				.as_ref()
-				.map(|wall: &ClosestWall| wall.distance)
-			{
-				let e = 0.0001;
-				// distance == closest_distance
-				if (distance - closest_distance).abs() < e {
+			let distance_squared =
+				(intersection.x - origin.x).powi(2) + (intersection.y - origin.y).powi(2);
+			if let Some(closest) = closest_wall.as_ref() {
+				if intersection == closest.intersection || walls_collinear(wall, &closest.wall) {
 					if !IS_TIEBREAKER {
 						ties.push(Rc::clone(wall));
+					} else if wall.index < closest.wall.index {
+						// Walls that are still tied can't be told apart. Pick one independent of the iteration order.
+						closest_wall = Some(ClosestWall {
+							wall: Rc::clone(wall),
+							intersection,
+							distance_squared,
+						});
 					}
-				} else if distance < closest_distance {
+				} else if distance_squared < closest.distance_squared {
 					second_closest_wall = closest_wall;
 					closest_wall = Some(ClosestWall {
 						wall: Rc::clone(wall),
 						intersection,
-						distance,
+						distance_squared,
 					});
 					swap(&mut ties, &mut second_closest_ties);
 					ties.clear();
-				} else if let Some(second_closest_distance) =
-					second_closest_wall.as_ref().map(|wall| wall.distance)
-				{
-					// distance == second_closest_distance
-					if (distance - second_closest_distance).abs() < e {
+				} else if let Some(second_closest) = second_closest_wall.as_ref() {
+					if intersection == second_closest.intersection
+						|| walls_collinear(wall, &second_closest.wall)
+					{
 						if !IS_TIEBREAKER {
 							second_closest_ties.push(Rc::clone(wall));
 						}
-					} else if distance < second_closest_distance {
+					} else if distance_squared < second_closest.distance_squared {
 						second_closest_wall = Some(ClosestWall {
 							wall: Rc::clone(wall),
 							intersection,
-							distance,
+							distance_squared,
 						});
 						second_closest_ties.clear();
 					}
@@ -258,14 +414,14 @@ where
 					second_closest_wall = Some(ClosestWall {
 						wall: Rc::clone(wall),
 						intersection,
-						distance,
+						distance_squared,
 					});
 				}
 			} else {
 				closest_wall = Some(ClosestWall {
 					wall: Rc::clone(wall),
 					intersection,
-					distance,
+					distance_squared,
 				});
 			}
 		}
@@ -273,7 +429,11 @@ where
 	if !IS_TIEBREAKER && ties.len() > 0 {
 		let closest_wall = closest_wall.as_mut().unwrap();
 		ties.push(Rc::clone(&closest_wall.wall));
-		closest_wall.wall = find_closest_wall_tiebreaker(origin, &ties).unwrap().wall;
+		closest_wall.wall = find_closest_wall_tiebreaker(origin, &ties)?
+			.ok_or(RaycastingError::MissingWallIntersection {
+				wall: closest_wall.wall.index,
+			})?
+			.wall;
 	}
 	if let Some(closest_wall_ref) = &mut closest_wall {
 		if !IS_TIEBREAKER && closest_wall_ref.wall.sense == WallSenseType::LIMITED {
@@ -282,33 +442,42 @@ where
 					.into_iter()
 					.filter(|wall| !Rc::ptr_eq(wall, &closest_wall_ref.wall))
 					.collect();
-				closest_wall_ref.wall = find_closest_wall_tiebreaker(origin, &ties).unwrap().wall;
+				closest_wall_ref.wall = find_closest_wall_tiebreaker(origin, &ties)?
+					.ok_or(RaycastingError::MissingWallIntersection {
+						wall: closest_wall_ref.wall.index,
+					})?
+					.wall;
 			} else if second_closest_ties.len() > 0 {
 				closest_wall = second_closest_wall;
 				let closest_wall = closest_wall.as_mut().unwrap();
 				second_closest_ties.push(Rc::clone(&closest_wall.wall));
-				closest_wall.wall = find_closest_wall_tiebreaker(origin, &second_closest_ties)
-					.unwrap()
+				closest_wall.wall = find_closest_wall_tiebreaker(origin, &second_closest_ties)?
+					.ok_or(RaycastingError::MissingWallIntersection {
+						wall: closest_wall.wall.index,
+					})?
 					.wall;
 			} else {
 				closest_wall = second_closest_wall;
 			}
 		}
 	}
-	closest_wall
+	Ok(closest_wall)
 }
 
-fn find_closest_wall_tiebreaker(origin: Point, ties: &Vec<Rc<Wall>>) -> Option<ClosestWall> {
-	let first_ending_wall = ties
-		.iter()
-		.reduce(|w1, w2| {
-			if is_smaller_relative(w1.end.borrow().angle, w2.end.borrow().angle) {
-				w1
-			} else {
-				w2
-			}
-		})
-		.unwrap();
+fn find_closest_wall_tiebreaker(
+	origin: Point,
+	ties: &Vec<Rc<Wall>>,
+) -> Result<Option<ClosestWall>, RaycastingError> {
+	let first_ending_wall = match ties.iter().reduce(|w1, w2| {
+		if is_smaller_relative(w1.end.borrow().angle, w2.end.borrow().angle) {
+			w1
+		} else {
+			w2
+		}
+	}) {
+		Some(wall) => wall,
+		None => return Ok(None),
+	};
 	let ray_to_endpoint = Line::from_points(origin, first_ending_wall.end.borrow().point);
-	find_closest_wall::<_, true>(origin, &ray_to_endpoint, ties)
+	find_closest_wall::<_, true>(origin, &ray_to_endpoint, None, ties)
 }
@@ -1,18 +1,22 @@
 use rustc_hash::FxHashMap;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-use crate::geometry::{Line, Point};
-use crate::raycasting::util::{is_intersection_on_wall, is_smaller_relative};
+use crate::geometry::{orient2d, Line, Point};
+use crate::raycasting::util::{is_smaller_relative, walls_cross, LineSegment};
+use crate::raycasting::wall_grid::WallGrid;
 use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::f64::consts::PI;
+use std::ops::AddAssign;
 use std::rc::Rc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct ClosestWall {
 	pub wall: Rc<Wall>,
 	pub intersection: Point,
-	pub distance: f64,
+	pub distance_squared: f64,
 }
 
 impl PartialEq for ClosestWall {
@@ -29,7 +33,8 @@ pub struct Endpoint {
 	pub angle: f64,
 	pub starting_walls: Vec<Rc<Wall>>,
 	pub ending_walls: Vec<Rc<Wall>>,
-	pub is_intersection: bool,
+	/// The indices of the walls that cross each other at this point
+	pub crossing_walls: Vec<usize>,
 }
 
 impl Endpoint {
@@ -44,12 +49,29 @@ impl Endpoint {
 			angle,
 			starting_walls: Vec::new(),
 			ending_walls: Vec::new(),
-			is_intersection: false,
+			crossing_walls: Vec::new(),
 		}
 	}
+
+	pub fn is_intersection(&self) -> bool {
+		!self.crossing_walls.is_empty()
+	}
+
+	/// The intersection of the wall with the ray from the origin through this endpoint
+	pub fn ray_intersection(&self, ray: &Line, wall: &Wall) -> Option<Point> {
+		// If this endpoint lies on the wall, the ray hits the wall exactly here. This is checked explicitly because
+		// the computed intersection of two walls isn't exactly on both walls and the intersection of the ray with a wall
+		// that this endpoint touches (like in a T-junction) may be rounded to a different point.
+		if self.crossing_walls.contains(&wall.index)
+			|| orient2d(wall.p1, wall.p2, self.point) == 0.0
+		{
+			return Some(self.point);
+		}
+		ray.intersection(&wall.line())
+	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DoorState {
 	CLOSED = 0,
@@ -69,7 +91,7 @@ impl TryFrom<usize> for DoorState {
 	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DoorType {
 	NONE = 0,
@@ -89,23 +111,55 @@ impl TryFrom<usize> for DoorType {
 	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[allow(dead_code)]
 pub struct ExposedEndpoint {
 	pub x: f64,
 	pub y: f64,
 	pub angle: f64,
-	#[wasm_bindgen(js_name=isIntersection)]
+	#[cfg_attr(feature = "wasm", wasm_bindgen(js_name=isIntersection))]
 	pub is_intersection: bool,
 }
 
+/// Intermediate results of a polygon computation that are useful for debugging
+#[derive(Default)]
+pub struct Internals {
+	pub endpoints: Vec<ExposedEndpoint>,
+	/// The phases are only timed if this is set beforehand, because the clock can't be read in every environment
+	pub timings: Option<PhaseTimings>,
+	/// Checks the polygons using `validate_polygons` and returns the first defect as an error.
+	/// The check is quadratic in the number of points, so release builds only run it if this is set.
+	/// Debug builds always run it.
+	pub validate: bool,
+}
+
+/// How long each phase of a polygon computation took
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PhaseTimings {
+	pub prepare_data: Duration,
+	/// Includes adding the vision wedge
+	pub calculate_los: Duration,
+	pub calculate_fov: Duration,
+	/// Both polygons together
+	pub fill_gaps: Duration,
+}
+
+impl AddAssign for PhaseTimings {
+	fn add_assign(&mut self, other: Self) {
+		self.prepare_data += other.prepare_data;
+		self.calculate_los += other.calculate_los;
+		self.calculate_fov += other.calculate_fov;
+		self.fill_gaps += other.fill_gaps;
+	}
+}
+
 impl From<&Endpoint> for ExposedEndpoint {
 	fn from(endpoint: &Endpoint) -> Self {
 		Self {
 			x: endpoint.point.x,
 			y: endpoint.point.y,
 			angle: endpoint.angle,
-			is_intersection: endpoint.is_intersection,
+			is_intersection: endpoint.is_intersection(),
 		}
 	}
 }
@@ -115,6 +169,28 @@ pub struct FovPoint {
 	pub point: Point,
 	pub angle: f64,
 	pub gap: bool,
+	/// The source of the edge to the next point. Ignored if `gap` is set, because gaps are filled by an arc.
+	pub edge: EdgeSource,
+}
+
+/// Describes what bounds an edge of a computed polygon
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EdgeSource {
+	/// The edge lies on the wall with this index in `Cache::walls`
+	Wall(usize),
+	/// The edge is part of the arc that closes gaps at the radius of the polygon
+	Arc,
+	/// The edge is a side of the vision cone
+	Wedge,
+	/// The edge lies on a ray from the origin and connects a wall with whatever is visible behind it
+	Shadow,
+}
+
+/// A polygon together with the source of each of its edges. `edges[i]` is the edge from `points[i]` to the next point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedPolygon {
+	pub points: Vec<Point>,
+	pub edges: Vec<EdgeSource>,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -139,6 +215,19 @@ impl TryFrom<usize> for PolygonType {
 	}
 }
 
+/// The parameters of a single polygon computation, used to compute the polygons of many sources at once
+#[derive(Copy, Clone)]
+pub struct PolygonSource {
+	pub origin: Point,
+	pub height: f64,
+	pub radius: f64,
+	pub distance: f64,
+	pub density: f64,
+	pub angle: f64,
+	pub rotation: f64,
+	pub polygon_type: PolygonType,
+}
+
 pub struct VisionAngle {
 	pub start: f64,
 	pub end: f64,
@@ -181,9 +270,9 @@ impl VisionAngle {
 }
 
 pub struct Wall {
+	pub index: usize,
 	pub p1: Point,
 	pub p2: Point,
-	pub line: Line,
 	pub sense: WallSenseType,
 	pub see_through_angle: Option<f64>,
 	pub end: Rc<RefCell<Endpoint>>,
@@ -191,31 +280,18 @@ pub struct Wall {
 
 impl Wall {
 	pub fn from_base(
+		index: usize,
 		base: WallBase,
 		end: Rc<RefCell<Endpoint>>,
 		cache: &Cache,
 		polygon_type: PolygonType,
 	) -> Self {
-		let see_through_angle;
-		if base.dir == WallDirection::BOTH {
-			see_through_angle = None;
-		} else {
-			let offset = match base.dir {
-				WallDirection::LEFT => 0.0,
-				WallDirection::RIGHT => PI,
-				WallDirection::BOTH => unreachable!(),
-			};
-			let mut angle = (base.p1.y - base.p2.y).atan2(base.p1.x - base.p2.x) + offset;
-			if angle > PI {
-				angle -= 2.0 * PI;
-			}
-			see_through_angle = Some(angle);
-		}
+		let see_through_angle = base.see_through_angle();
 		let sense = base.current_sense(&cache, polygon_type);
 		Self {
+			index,
 			p1: base.p1,
 			p2: base.p2,
-			line: base.line,
 			sense,
 			see_through_angle,
 			end,
@@ -237,18 +313,15 @@ impl std::fmt::Debug for Wall {
 		f.debug_struct("Wall")
 			.field("p1", &self.p1)
 			.field("p2", &self.p2)
-			.field("line", &self.line)
 			.finish()
 	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Copy, Clone)]
 pub struct WallBase {
 	pub p1: Point,
 	pub p2: Point,
-	#[wasm_bindgen(skip)]
-	pub line: Line,
 	pub movement: WallSenseType,
 	pub sense: WallSenseType,
 	pub sound: WallSenseType,
@@ -272,11 +345,9 @@ impl WallBase {
 		height: WallHeight,
 		roof: Option<TileId>,
 	) -> Self {
-		let line = Line::from_points(p1, p2);
 		Self {
 			p1,
 			p2,
-			line,
 			movement,
 			sense,
 			sound,
@@ -302,9 +373,39 @@ impl WallBase {
 			}
 		}
 	}
+
+	/// Like `current_sense`, but also takes open doors and the wall's height into account
+	pub fn effective_sense(
+		&self,
+		cache: &Cache,
+		height: f64,
+		polygon_type: PolygonType,
+	) -> WallSenseType {
+		if self.door != DoorType::NONE && self.ds == DoorState::OPEN {
+			return WallSenseType::NONE;
+		}
+		if self.height.bottom > height || self.height.top < height {
+			return WallSenseType::NONE;
+		}
+		self.current_sense(cache, polygon_type)
+	}
+
+	// Rays with an angle that is smaller relative to this angle pass through the wall. `None` if the wall isn't directional.
+	pub fn see_through_angle(&self) -> Option<f64> {
+		let offset = match self.dir {
+			WallDirection::BOTH => return None,
+			WallDirection::LEFT => 0.0,
+			WallDirection::RIGHT => PI,
+		};
+		let mut angle = (self.p1.y - self.p2.y).atan2(self.p1.x - self.p2.x) + offset;
+		if angle > PI {
+			angle -= 2.0 * PI;
+		}
+		Some(angle)
+	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Copy, Clone)]
 pub struct WallHeight {
 	pub top: f64,
@@ -320,7 +421,7 @@ impl Default for WallHeight {
 	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum WallDirection {
 	BOTH = 0,
@@ -340,7 +441,7 @@ impl TryFrom<usize> for WallDirection {
 	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum WallSenseType {
 	NONE = 0,
@@ -362,11 +463,11 @@ impl TryFrom<usize> for WallSenseType {
 
 #[derive(Copy, Clone)]
 pub struct WallWithAngles {
+	pub index: usize,
 	pub p1: Point,
 	pub p2: Point,
 	pub angle_p1: f64,
 	pub angle_p2: f64,
-	pub line: Line,
 	pub sense: WallSenseType,
 	pub see_through_angle: Option<f64>,
 }
@@ -380,11 +481,11 @@ impl WallWithAngles {
 		angle_p2: f64,
 	) -> Self {
 		Self {
+			index: prop_src.index,
 			p1,
 			p2,
 			angle_p1,
 			angle_p2,
-			line: Line::from_points(p1, p2),
 			sense: prop_src.sense,
 			see_through_angle: prop_src.see_through_angle,
 		}
@@ -392,9 +493,9 @@ impl WallWithAngles {
 
 	pub fn to_wall(self, end: Rc<RefCell<Endpoint>>) -> Wall {
 		Wall {
+			index: self.index,
 			p1: self.p1,
 			p2: self.p2,
-			line: self.line,
 			sense: self.sense,
 			see_through_angle: self.see_through_angle,
 			end,
@@ -405,61 +506,200 @@ impl WallWithAngles {
 pub struct Intersection {
 	pub point: Point,
 	pub height: WallHeight,
+	pub walls: (usize, usize),
 }
 
 // TODO Locate this into a different module
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Cache {
-	#[wasm_bindgen(skip)]
+	#[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
 	pub walls: Vec<WallBase>,
-	#[wasm_bindgen(skip)]
+	#[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
 	pub intersections: Vec<Intersection>,
-	#[wasm_bindgen(skip)]
+	#[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
 	pub tiles: TileCache,
+	#[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+	pub wall_ids: FxHashMap<String, usize>,
+	#[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+	pub grid: WallGrid,
 }
 
 impl Cache {
 	pub fn build(walls: Vec<WallBase>, tiles: TileCache) -> Self {
-		let intersections = Self::calc_intersections(&walls);
+		let grid = WallGrid::build(&walls);
+		let intersections = Self::calc_intersections(&walls, &grid);
 		Self {
 			walls,
 			intersections,
 			tiles,
+			wall_ids: FxHashMap::default(),
+			grid,
 		}
 	}
 
-	fn calc_intersections(walls: &Vec<WallBase>) -> Vec<Intersection> {
-		use partial_min_max::{max, min};
+	/// Adds a wall to the cache. If a wall with the same id is already present, it is replaced and the old wall is returned.
+	pub fn insert_wall(&mut self, id: String, wall: WallBase) -> Option<WallBase> {
+		if let Some(&index) = self.wall_ids.get(&id) {
+			return Some(self.replace_wall_at(index, wall));
+		}
+		let index = self.walls.len();
+		self.walls.push(wall);
+		self.wall_ids.insert(id, index);
+		self.grid.insert(index, wall.p1, wall.p2);
+		self.add_intersections_of(index);
+		None
+	}
+
+	/// Replaces the wall with the given id. Returns the old wall, or `None` if there is no wall with that id.
+	pub fn replace_wall(&mut self, id: &str, wall: WallBase) -> Option<WallBase> {
+		let index = *self.wall_ids.get(id)?;
+		Some(self.replace_wall_at(index, wall))
+	}
+
+	/// Changes the door state of the wall with the given id. Returns `false` if there is no wall with that id.
+	pub fn set_door_state(&mut self, id: &str, ds: DoorState) -> bool {
+		if let Some(&index) = self.wall_ids.get(id) {
+			// The door state doesn't influence the intersections, so they don't need to be updated
+			self.walls[index].ds = ds;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Removes the wall with the given id from the cache and returns it
+	pub fn remove_wall(&mut self, id: &str) -> Option<WallBase> {
+		let index = self.wall_ids.remove(id)?;
+		self.intersections
+			.retain(|intersection| intersection.walls.0 != index && intersection.walls.1 != index);
+		let wall = self.walls.swap_remove(index);
+		self.grid.remove(index, wall.p1, wall.p2);
+
+		// The last wall has been moved into the freed slot, so everything referencing it needs to be updated
+		let moved_index = self.walls.len();
+		if index != moved_index {
+			let moved_wall = self.walls[index];
+			self.grid.remove(moved_index, moved_wall.p1, moved_wall.p2);
+			self.grid.insert(index, moved_wall.p1, moved_wall.p2);
+			for wall_index in self.wall_ids.values_mut() {
+				if *wall_index == moved_index {
+					*wall_index = index;
+					break;
+				}
+			}
+			for intersection in &mut self.intersections {
+				if intersection.walls.0 == moved_index {
+					intersection.walls.0 = index;
+				}
+				if intersection.walls.1 == moved_index {
+					intersection.walls.1 = index;
+				}
+			}
+		}
+		Some(wall)
+	}
+
+	fn replace_wall_at(&mut self, index: usize, wall: WallBase) -> WallBase {
+		let old_wall = std::mem::replace(&mut self.walls[index], wall);
+		self.grid.remove(index, old_wall.p1, old_wall.p2);
+		self.grid.insert(index, wall.p1, wall.p2);
+		self.intersections
+			.retain(|intersection| intersection.walls.0 != index && intersection.walls.1 != index);
+		self.add_intersections_of(index);
+		old_wall
+	}
+
+	fn add_intersections_of(&mut self, index: usize) {
+		let wall = &self.walls[index];
+		let mut candidates = Vec::new();
+		self.grid
+			.segment_candidates(wall.p1, wall.p2, &mut candidates);
+		for other in candidates {
+			if other == index {
+				continue;
+			}
+			let (i, j) = if other < index {
+				(other, index)
+			} else {
+				(index, other)
+			};
+			if let Some(intersection) = Self::calc_intersection(&self.walls, i, j) {
+				self.intersections.push(intersection);
+			}
+		}
+	}
+
+	fn calc_intersections(walls: &[WallBase], grid: &WallGrid) -> Vec<Intersection> {
+		// Only walls that share a cell in the grid can intersect each other
+		let mut intersections = Vec::new();
+		let mut candidates = Vec::new();
+		for (i, wall) in walls.iter().enumerate() {
+			grid.segment_candidates(wall.p1, wall.p2, &mut candidates);
+			for &j in candidates.iter().filter(|&&j| j > i) {
+				if let Some(intersection) = Self::calc_intersection(walls, i, j) {
+					intersections.push(intersection);
+				}
+			}
+		}
+		intersections
+	}
+
+	#[cfg(test)]
+	pub fn calc_intersections_brute_force(walls: &[WallBase]) -> Vec<Intersection> {
 		let mut intersections = Vec::new();
 		if walls.len() >= 2 {
 			for i in 0..walls.len() - 1 {
-				for j in 0..walls.len() - i - 1 {
-					let (i_walls, j_walls) = walls.split_at(i + 1);
-					let wall1 = &i_walls[i];
-					let wall2 = &j_walls[j];
-					let bottom = max(wall1.height.bottom, wall2.height.bottom);
-					let top = min(wall1.height.top, wall2.height.top);
-					if bottom <= top {
-						let point = wall1.line.intersection(&wall2.line);
-						match point {
-							Some(point) => {
-								if is_intersection_on_wall(point, wall1)
-									&& is_intersection_on_wall(point, wall2)
-								{
-									intersections.push(Intersection {
-										point,
-										height: WallHeight { top, bottom },
-									});
-								}
-							}
-							None => {}
-						};
+				for j in i + 1..walls.len() {
+					if let Some(intersection) = Self::calc_intersection(walls, i, j) {
+						intersections.push(intersection);
 					}
 				}
 			}
 		}
 		intersections
 	}
+
+	fn calc_intersection(walls: &[WallBase], i: usize, j: usize) -> Option<Intersection> {
+		use partial_min_max::{max, min};
+		let wall1 = &walls[i];
+		let wall2 = &walls[j];
+		let bottom = max(wall1.height.bottom, wall2.height.bottom);
+		let top = min(wall1.height.top, wall2.height.top);
+		if bottom > top {
+			return None;
+		}
+		if !walls_cross(wall1, wall2) {
+			return None;
+		}
+		// The computed point only depends on the geometry of the walls. That way identical walls that are crossed by the same wall
+		// produce exactly the same point, which is important because those points are treated as the same crossing.
+		let line1 = canonical_line(wall1);
+		let line2 = canonical_line(wall2);
+		let (line1, line2) = if line_key(&line1) <= line_key(&line2) {
+			(line1, line2)
+		} else {
+			(line2, line1)
+		};
+		let point = line1.intersection(&line2)?;
+		Some(Intersection {
+			point,
+			height: WallHeight { top, bottom },
+			walls: (i, j),
+		})
+	}
+}
+
+// The line through the wall's points, with the points in a fixed order
+fn canonical_line(wall: &WallBase) -> Line {
+	if (wall.p1.x, wall.p1.y) <= (wall.p2.x, wall.p2.y) {
+		Line::from_points(wall.p1, wall.p2)
+	} else {
+		Line::from_points(wall.p2, wall.p1)
+	}
+}
+
+fn line_key(line: &Line) -> (f64, f64, f64, f64) {
+	(line.p1.x, line.p1.y, line.p2.x, line.p2.y)
 }
 
 pub type TileId = usize;
@@ -477,4 +717,14 @@ impl TileCache {
 			..Self::default()
 		}
 	}
+
+	/// Looks up the internal id of a tile, registering the tile if it isn't known yet
+	pub fn get_or_insert(&mut self, id: String, occluded: bool) -> TileId {
+		let next_id = self.occluded.len();
+		let occluded_list = &mut self.occluded;
+		*self.id_map.entry(id).or_insert_with(|| {
+			occluded_list.push(occluded);
+			next_id
+		})
+	}
 }
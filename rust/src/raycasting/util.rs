@@ -1,14 +1,9 @@
-use crate::geometry::{Line, Point};
-use crate::raycasting::types::Wall;
+use crate::geometry::{orient2d, segments_cross, Line, Point};
+use crate::raycasting::types::{Wall, WallWithAngles};
 use crate::raycasting::WallBase;
 use std::f64::consts::PI;
 use std::rc::Rc;
 
-pub fn between<T: Copy + PartialOrd>(num: T, a: T, b: T) -> bool {
-	let (min, max) = if a < b { (a, b) } else { (b, a) };
-	num >= min && num <= max
-}
-
 pub fn between_exclusive<T: Copy + PartialOrd>(num: T, a: T, b: T) -> bool {
 	let (min, max) = if a < b { (a, b) } else { (b, a) };
 	num > min && num < max
@@ -24,18 +19,16 @@ pub fn is_smaller_relative(angle1: f64, angle2: f64) -> bool {
 	return angle_distance > 0.0;
 }
 
-// TODO Use a segment class instead of this weird trait
 pub trait LineSegment {
-	fn line(&self) -> Line;
 	fn p1(&self) -> Point;
 	fn p2(&self) -> Point;
-}
 
-impl LineSegment for Wall {
 	fn line(&self) -> Line {
-		self.line
+		Line::from_points(self.p1(), self.p2())
 	}
+}
 
+impl LineSegment for Wall {
 	fn p1(&self) -> Point {
 		self.p1
 	}
@@ -45,11 +38,17 @@ impl LineSegment for Wall {
 	}
 }
 
-impl LineSegment for WallBase {
-	fn line(&self) -> Line {
-		self.line
+impl LineSegment for WallWithAngles {
+	fn p1(&self) -> Point {
+		self.p1
 	}
 
+	fn p2(&self) -> Point {
+		self.p2
+	}
+}
+
+impl LineSegment for WallBase {
 	fn p1(&self) -> Point {
 		self.p1
 	}
@@ -60,10 +59,6 @@ impl LineSegment for WallBase {
 }
 
 impl<T: LineSegment> LineSegment for Rc<T> {
-	fn line(&self) -> Line {
-		self.as_ref().line()
-	}
-
 	fn p1(&self) -> Point {
 		self.as_ref().p1()
 	}
@@ -73,16 +68,12 @@ impl<T: LineSegment> LineSegment for Rc<T> {
 	}
 }
 
-pub fn is_intersection_on_wall<S: LineSegment>(intersection: Point, wall: &S) -> bool {
-	is_intersection_on_segment(intersection, wall.line(), wall.p1(), wall.p2())
+/// Checks if both walls are on the same line. Such walls always hit a ray at the same point.
+pub fn walls_collinear<A: LineSegment, B: LineSegment>(wall1: &A, wall2: &B) -> bool {
+	orient2d(wall1.p1(), wall1.p2(), wall2.p1()) == 0.0
+		&& orient2d(wall1.p1(), wall1.p2(), wall2.p2()) == 0.0
 }
 
-pub fn is_intersection_on_segment(intersection: Point, line: Line, p1: Point, p2: Point) -> bool {
-	if intersection.is_same_as(&p1) || intersection.is_same_as(&p2) {
-		return false;
-	}
-	if line.is_vertical() || line.m.abs() > 1.0 {
-		return between(intersection.y, p1.y, p2.y);
-	}
-	between(intersection.x, p1.x, p2.x)
+pub fn walls_cross<A: LineSegment, B: LineSegment>(wall1: &A, wall2: &B) -> bool {
+	segments_cross(wall1.p1(), wall1.p2(), wall2.p1(), wall2.p2())
 }
@@ -0,0 +1,191 @@
+use crate::geometry::{
+	distance_to_polygon_boundary, orient2d, polygon_contains, segments_cross, Point,
+};
+use std::f64::consts::PI;
+use std::fmt::{self, Display, Formatter};
+
+// The angle in radians by which an edge may run clockwise around the origin before it counts as a defect
+const ANGLE_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PolygonKind {
+	Los,
+	Fov,
+}
+
+/// A violated invariant of a polygon returned by `compute_polygon`. Edge `i` leads from point `i` to point `i + 1`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PolygonDefect {
+	/// The polygon has less than three points
+	TooFewPoints { polygon: PolygonKind },
+	/// A point doesn't have finite coordinates
+	NotFinite { polygon: PolygonKind, index: usize },
+	/// Two edges cross each other
+	SelfIntersection {
+		polygon: PolygonKind,
+		edges: (usize, usize),
+	},
+	/// The edge runs around the origin clockwise, while polygons are expected to run counter clockwise
+	InconsistentWinding { polygon: PolygonKind, edge: usize },
+	/// The polygon runs around the origin more than once, so it isn't star-shaped around the origin
+	NotStarShaped { polygon: PolygonKind, turns: f64 },
+	/// The origin lies outside of the polygon
+	OriginOutside { polygon: PolygonKind },
+	/// A point of the FOV lies outside of the LOS
+	FovOutsideLos { index: usize },
+	/// A point of the FOV is further away from the origin than the radius
+	FovOutsideRadius { index: usize },
+}
+
+impl Display for PolygonKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Los => write!(f, "LOS"),
+			Self::Fov => write!(f, "FOV"),
+		}
+	}
+}
+
+impl Display for PolygonDefect {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::TooFewPoints { polygon } => write!(f, "The {} has less than 3 points", polygon),
+			Self::NotFinite { polygon, index } => write!(
+				f,
+				"Point {} of the {} doesn't have finite coordinates",
+				index, polygon
+			),
+			Self::SelfIntersection { polygon, edges } => write!(
+				f,
+				"Edges {} and {} of the {} cross each other",
+				edges.0, edges.1, polygon
+			),
+			Self::InconsistentWinding { polygon, edge } => write!(
+				f,
+				"Edge {} of the {} runs around the origin clockwise",
+				edge, polygon
+			),
+			Self::NotStarShaped { polygon, turns } => {
+				write!(f, "The {} runs around the origin {} times", polygon, turns)
+			}
+			Self::OriginOutside { polygon } => {
+				write!(f, "The origin is outside of the {}", polygon)
+			}
+			Self::FovOutsideLos { index } => {
+				write!(f, "Point {} of the FOV is outside of the LOS", index)
+			}
+			Self::FovOutsideRadius { index } => write!(
+				f,
+				"Point {} of the FOV is further away from the origin than the radius",
+				index
+			),
+		}
+	}
+}
+
+/// Checks the polygons returned by `compute_polygon` for defects. An empty result means that the polygons are valid.
+/// `distance` and `density` are needed to tell apart the arcs of both polygons, which are only approximated.
+pub fn validate_polygons(
+	origin: Point,
+	radius: f64,
+	distance: f64,
+	density: f64,
+	los: &[Point],
+	fov: &[Point],
+) -> Vec<PolygonDefect> {
+	let mut defects = Vec::new();
+	validate_polygon(PolygonKind::Los, origin, los, &mut defects);
+	validate_polygon(PolygonKind::Fov, origin, fov, &mut defects);
+	if !defects.is_empty() {
+		// The remaining checks rely on both polygons being valid
+		return defects;
+	}
+
+	// Arcs are approximated by chords, so the arc of the FOV may bulge out of the arc of the LOS by the height of a chord
+	let scale = 1.0 + origin.x.abs().max(origin.y.abs()) + radius.min(distance);
+	let tolerance =
+		distance.min(radius) * (1.0 - (density.to_radians() / 2.0).cos()) + scale * 1e-9;
+	for (index, point) in fov.iter().enumerate() {
+		if point.distance_to(&origin) > radius + scale * 1e-9 {
+			defects.push(PolygonDefect::FovOutsideRadius { index });
+		}
+		if !polygon_contains(los, *point) && distance_to_polygon_boundary(los, *point) > tolerance {
+			defects.push(PolygonDefect::FovOutsideLos { index });
+		}
+	}
+	defects
+}
+
+fn validate_polygon(
+	kind: PolygonKind,
+	origin: Point,
+	polygon: &[Point],
+	defects: &mut Vec<PolygonDefect>,
+) {
+	if polygon.len() < 3 {
+		defects.push(PolygonDefect::TooFewPoints { polygon: kind });
+		return;
+	}
+	if let Some(index) = polygon
+		.iter()
+		.position(|point| !point.x.is_finite() || !point.y.is_finite())
+	{
+		defects.push(PolygonDefect::NotFinite {
+			polygon: kind,
+			index,
+		});
+		return;
+	}
+
+	let edge = |i: usize| (polygon[i], polygon[(i + 1) % polygon.len()]);
+
+	// Sum up the angles the edges cover around the origin. Edges that touch the origin don't cover any angle.
+	let mut origin_on_boundary = false;
+	let mut total_angle = 0.0;
+	for i in 0..polygon.len() {
+		let (p1, p2) = edge(i);
+		let touches_origin = orient2d(origin, p1, p2) == 0.0
+			&& origin.x >= p1.x.min(p2.x)
+			&& origin.x <= p1.x.max(p2.x)
+			&& origin.y >= p1.y.min(p2.y)
+			&& origin.y <= p1.y.max(p2.y);
+		if touches_origin {
+			origin_on_boundary = true;
+			continue;
+		}
+		let v1 = Point::new(p1.x - origin.x, p1.y - origin.y);
+		let v2 = Point::new(p2.x - origin.x, p2.y - origin.y);
+		let angle = (v1.x * v2.y - v1.y * v2.x).atan2(v1.x * v2.x + v1.y * v2.y);
+		// Points where a ray hits a wall are rounded, so edges along a ray may turn backwards by a tiny angle
+		if angle < -ANGLE_TOLERANCE {
+			defects.push(PolygonDefect::InconsistentWinding {
+				polygon: kind,
+				edge: i,
+			});
+		}
+		total_angle += angle;
+	}
+	let turns = total_angle / (2.0 * PI);
+	if turns > 1.0 + 1e-6 {
+		defects.push(PolygonDefect::NotStarShaped {
+			polygon: kind,
+			turns,
+		});
+	} else if turns < 1.0 - 1e-6 && !origin_on_boundary {
+		defects.push(PolygonDefect::OriginOutside { polygon: kind });
+	}
+
+	// Edges that merely touch each other are fine, because points may repeat
+	for i in 0..polygon.len() {
+		let (a1, a2) = edge(i);
+		for j in i + 2..polygon.len() {
+			let (b1, b2) = edge(j);
+			if segments_cross(a1, a2, b1, b2) {
+				defects.push(PolygonDefect::SelfIntersection {
+					polygon: kind,
+					edges: (i, j),
+				});
+			}
+		}
+	}
+}
@@ -0,0 +1,54 @@
+use crate::geometry::{segment_intersection_parameters, Point};
+use crate::raycasting::types::{Cache, PolygonType, WallSenseType};
+use crate::raycasting::util::is_smaller_relative;
+
+// Checks if `target` can be seen from `origin`, following the same rules for walls that `compute_polygon` uses
+pub fn is_point_visible(
+	cache: &Cache,
+	origin: Point,
+	height: f64,
+	target: Point,
+	polygon_type: PolygonType,
+) -> bool {
+	if origin == target {
+		return true;
+	}
+	// The angle of the ray as it would be calculated for an endpoint in `compute_polygon`
+	let angle = (origin.y - target.y).atan2(origin.x - target.x);
+	let mut candidates = Vec::new();
+	cache
+		.grid
+		.segment_candidates(origin, target, &mut candidates);
+	let mut limited_wall_passed = false;
+	for index in candidates {
+		let wall = &cache.walls[index];
+		// compute_polygon ignores walls that touch the origin
+		if wall.p1 == origin || wall.p2 == origin {
+			continue;
+		}
+		let sense = wall.effective_sense(cache, height, polygon_type);
+		if sense == WallSenseType::NONE {
+			continue;
+		}
+		let (t, u) = match segment_intersection_parameters(origin, target, wall.p1, wall.p2) {
+			Some(parameters) => parameters,
+			None => continue,
+		};
+		// The wall needs to be between origin and target. Points that are located on a wall count as visible.
+		if t <= 0.0 || t >= 1.0 || !(0.0..=1.0).contains(&u) {
+			continue;
+		}
+		if let Some(see_through_angle) = wall.see_through_angle() {
+			if is_smaller_relative(angle, see_through_angle) {
+				continue;
+			}
+		}
+		// Limited walls only block vision if there is another wall behind them
+		if sense == WallSenseType::LIMITED && !limited_wall_passed {
+			limited_wall_passed = true;
+			continue;
+		}
+		return false;
+	}
+	true
+}
@@ -1,16 +1,17 @@
 use crate::geometry::Point;
-use crate::raycasting::types::{Endpoint, FovPoint, VisionAngle, Wall, WallWithAngles};
+use crate::raycasting::error::RaycastingError;
+use crate::raycasting::types::{EdgeSource, Endpoint, FovPoint, VisionAngle, Wall, WallWithAngles};
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use super::util::between_exclusive;
+use super::util::{between_exclusive, LineSegment};
 
 pub fn restrict_vision_angle(
 	wall: &Wall,
 	start: &Rc<RefCell<Endpoint>>,
 	end: &Rc<RefCell<Endpoint>>,
 	vision_angle: &Option<VisionAngle>,
-) -> Option<[Option<WallWithAngles>; 2]> {
+) -> Result<Option<[Option<WallWithAngles>; 2]>, RaycastingError> {
 	if let Some(vision_angle) = vision_angle {
 		if vision_angle.start < vision_angle.end {
 			let wall_inverted;
@@ -18,14 +19,14 @@ pub fn restrict_vision_angle(
 				if start.borrow().angle >= vision_angle.end
 					|| end.borrow().angle <= vision_angle.start
 				{
-					return Some([None, None]);
+					return Ok(Some([None, None]));
 				}
 				wall_inverted = false;
 			} else {
 				if end.borrow().angle <= vision_angle.start
 					&& start.borrow().angle >= vision_angle.end
 				{
-					return Some([None, None]);
+					return Ok(Some([None, None]));
 				}
 				wall_inverted = true;
 			}
@@ -39,12 +40,12 @@ pub fn restrict_vision_angle(
 				if start.borrow().angle < vision_angle.start
 					|| end.borrow().angle > vision_angle.end
 				{
-					if let Some(intersection) = vision_angle.start_ray.intersection(&wall.line) {
+					if let Some(intersection) = vision_angle.start_ray.intersection(&wall.line()) {
 						wall_shortened = true;
 						start_angle = vision_angle.start;
 						start_point = intersection;
 					}
-					if let Some(intersection) = vision_angle.end_ray.intersection(&wall.line) {
+					if let Some(intersection) = vision_angle.end_ray.intersection(&wall.line()) {
 						wall_shortened = true;
 						end_angle = vision_angle.end;
 						end_point = intersection;
@@ -54,7 +55,7 @@ pub fn restrict_vision_angle(
 				if end.borrow().angle > vision_angle.start
 					&& start.borrow().angle < vision_angle.start
 				{
-					if let Some(intersection) = vision_angle.start_ray.intersection(&wall.line) {
+					if let Some(intersection) = vision_angle.start_ray.intersection(&wall.line()) {
 						wall_shortened = true;
 						start_angle = vision_angle.start;
 						start_point = intersection;
@@ -62,7 +63,7 @@ pub fn restrict_vision_angle(
 				}
 				if start.borrow().angle < vision_angle.end && end.borrow().angle > vision_angle.end
 				{
-					if let Some(intersection) = vision_angle.end_ray.intersection(&wall.line) {
+					if let Some(intersection) = vision_angle.end_ray.intersection(&wall.line()) {
 						wall_shortened = true;
 						end_angle = vision_angle.end;
 						end_point = intersection;
@@ -77,14 +78,16 @@ pub fn restrict_vision_angle(
 					start_angle,
 					end_angle,
 				);
-				return Some([Some(new_wall), None]);
+				return Ok(Some([Some(new_wall), None]));
 			}
 
 			if end.borrow().angle < start.borrow().angle {
 				// Only remaining option is that end.angle < end.start (which means the wall is to the right, where the circle overflows)
 				let mut split_walls = [None, None];
 				if end.borrow().angle > vision_angle.start {
-					let start_point = vision_angle.start_ray.intersection(&wall.line).unwrap();
+					let start_point = vision_angle.start_ray.intersection(&wall.line()).ok_or(
+						RaycastingError::MissingVisionAngleIntersection { wall: wall.index },
+					)?;
 					let start_angle = vision_angle.start;
 					let end_point = end.borrow().point;
 					let end_angle = end.borrow().angle;
@@ -99,7 +102,9 @@ pub fn restrict_vision_angle(
 				if start.borrow().angle < vision_angle.end {
 					let start_point = start.borrow().point;
 					let start_angle = start.borrow().angle;
-					let end_point = vision_angle.end_ray.intersection(&wall.line).unwrap();
+					let end_point = vision_angle.end_ray.intersection(&wall.line()).ok_or(
+						RaycastingError::MissingVisionAngleIntersection { wall: wall.index },
+					)?;
 					let end_angle = vision_angle.end;
 					split_walls[1] = Some(WallWithAngles::new_copy_props(
 						&wall,
@@ -117,9 +122,9 @@ pub fn restrict_vision_angle(
 						vision_angle.end,
 					) && !between_exclusive(end.borrow().angle, vision_angle.start, vision_angle.end)
 				{
-					return None;
+					return Ok(None);
 				}
-				return Some(split_walls);
+				return Ok(Some(split_walls));
 			}
 		} else {
 			if start.borrow().angle > end.borrow().angle {
@@ -129,19 +134,45 @@ pub fn restrict_vision_angle(
 				let mut end_point = end.borrow().point;
 				let mut end_angle = end.borrow().angle;
 				if start.borrow().angle < vision_angle.start {
-					if let Some(intersection) = vision_angle.start_ray.intersection(&wall.line) {
+					if let Some(intersection) = vision_angle.start_ray.intersection(&wall.line()) {
 						wall_shortened = true;
 						start_angle = vision_angle.start;
 						start_point = intersection;
 					}
 				}
 				if end.borrow().angle > vision_angle.end {
-					if let Some(intersection) = vision_angle.end_ray.intersection(&wall.line) {
+					if let Some(intersection) = vision_angle.end_ray.intersection(&wall.line()) {
 						wall_shortened = true;
 						end_angle = vision_angle.end;
 						end_point = intersection;
 					}
 				}
+				// The wall may leave the vision cone through the gap and enter it again on the other side of the gap.
+				// In that case the part on the other side of the gap is visible as well.
+				let mut reentering_wall = None;
+				if start.borrow().angle < vision_angle.end {
+					let reentry_point = vision_angle.end_ray.intersection(&wall.line()).ok_or(
+						RaycastingError::MissingVisionAngleIntersection { wall: wall.index },
+					)?;
+					reentering_wall = Some(WallWithAngles::new_copy_props(
+						&wall,
+						start.borrow().point,
+						reentry_point,
+						start.borrow().angle,
+						vision_angle.end,
+					));
+				} else if end.borrow().angle > vision_angle.start {
+					let reentry_point = vision_angle.start_ray.intersection(&wall.line()).ok_or(
+						RaycastingError::MissingVisionAngleIntersection { wall: wall.index },
+					)?;
+					reentering_wall = Some(WallWithAngles::new_copy_props(
+						&wall,
+						reentry_point,
+						end.borrow().point,
+						vision_angle.start,
+						end.borrow().angle,
+					));
+				}
 				if wall_shortened {
 					let new_wall = WallWithAngles::new_copy_props(
 						&wall,
@@ -150,14 +181,16 @@ pub fn restrict_vision_angle(
 						start_angle,
 						end_angle,
 					);
-					return Some([Some(new_wall), None]);
+					return Ok(Some([Some(new_wall), reentering_wall]));
 				}
 			} else {
 				let mut split_walls = [None, None];
 				if between_exclusive(vision_angle.end, start.borrow().angle, end.borrow().angle) {
 					let start_point = start.borrow().point;
 					let start_angle = start.borrow().angle;
-					let end_point = vision_angle.end_ray.intersection(&wall.line).unwrap();
+					let end_point = vision_angle.end_ray.intersection(&wall.line()).ok_or(
+						RaycastingError::MissingVisionAngleIntersection { wall: wall.index },
+					)?;
 					let end_angle = vision_angle.end;
 					split_walls[0] = Some(WallWithAngles::new_copy_props(
 						&wall,
@@ -168,7 +201,9 @@ pub fn restrict_vision_angle(
 					));
 				}
 				if between_exclusive(vision_angle.start, start.borrow().angle, end.borrow().angle) {
-					let start_point = vision_angle.start_ray.intersection(&wall.line).unwrap();
+					let start_point = vision_angle.start_ray.intersection(&wall.line()).ok_or(
+						RaycastingError::MissingVisionAngleIntersection { wall: wall.index },
+					)?;
 					let start_angle = vision_angle.start;
 					let end_point = end.borrow().point;
 					let end_angle = end.borrow().angle;
@@ -187,19 +222,19 @@ pub fn restrict_vision_angle(
 						vision_angle.end,
 					) && !between_exclusive(end.borrow().angle, vision_angle.start, vision_angle.end)
 				{
-					return None;
+					return Ok(None);
 				}
-				return Some(split_walls);
+				return Ok(Some(split_walls));
 			}
 		}
 	}
-	None
+	Ok(None)
 }
 
 pub fn add_vision_wedge(
 	mut los_points: Vec<FovPoint>,
 	origin: Point,
-	vision_angle: VisionAngle,
+	vision_angle: &VisionAngle,
 	start_gap_fov: &mut bool,
 ) -> Vec<FovPoint> {
 	let mut visible_points_from_start: &[FovPoint];
@@ -254,12 +289,14 @@ pub fn add_vision_wedge(
 			point: origin,
 			angle: vision_angle.start,
 			gap: false,
+			edge: EdgeSource::Wedge,
 		};
 	} else {
 		entry = FovPoint {
 			point: origin,
 			angle: vision_angle.start,
 			gap: true,
+			edge: EdgeSource::Wedge,
 		};
 	}
 
@@ -272,12 +309,14 @@ pub fn add_vision_wedge(
 		visible_points_to_end = remaining;
 		let mut point = *point;
 		point.gap = false;
+		point.edge = EdgeSource::Wedge;
 		exit = vec![
 			point,
 			FovPoint {
 				point: origin,
 				angle: vision_angle.end,
 				gap: false,
+				edge: EdgeSource::Wedge,
 			},
 		];
 	} else if !start_end_swapped
@@ -288,12 +327,14 @@ pub fn add_vision_wedge(
 		visible_points_from_start = remaining;
 		let mut point = *point;
 		point.gap = false;
+		point.edge = EdgeSource::Wedge;
 		exit = vec![
 			point,
 			FovPoint {
 				point: origin,
 				angle: vision_angle.end,
 				gap: false,
+				edge: EdgeSource::Wedge,
 			},
 		];
 	} else {
@@ -301,6 +342,7 @@ pub fn add_vision_wedge(
 			point: origin,
 			angle: vision_angle.end,
 			gap: false,
+			edge: EdgeSource::Wedge,
 		}];
 	}
 
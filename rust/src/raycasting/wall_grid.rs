@@ -0,0 +1,165 @@
+use crate::geometry::Point;
+use crate::raycasting::types::WallBase;
+use rustc_hash::FxHashMap;
+
+type CellId = (i32, i32);
+
+// Walls that would occupy more cells than this are stored outside of the grid and are always considered a candidate
+const MAX_CELLS_PER_WALL: f64 = 4096.0;
+
+const DEFAULT_CELL_SIZE: f64 = 100.0;
+
+/// A uniform grid over the walls of a scene. Each cell knows the indices of all walls that pass through it.
+/// This allows to quickly find walls that might be close to a given segment.
+pub struct WallGrid {
+	pub(crate) cell_size: f64,
+	pub(crate) cells: FxHashMap<CellId, Vec<usize>>,
+	pub(crate) oversized: Vec<usize>,
+}
+
+impl WallGrid {
+	pub fn new(cell_size: f64) -> Self {
+		Self {
+			cell_size,
+			cells: FxHashMap::default(),
+			oversized: Vec::new(),
+		}
+	}
+
+	pub fn build(walls: &[WallBase]) -> Self {
+		// Using the average wall length as cell size keeps the number of cells per wall low while also keeping the number of walls per cell low
+		let total_length: f64 = walls
+			.iter()
+			.map(|wall| wall.p1.distance_to(&wall.p2))
+			.filter(|length| length.is_finite())
+			.sum();
+		let mut cell_size = total_length / walls.len() as f64;
+		if !cell_size.is_finite() || cell_size <= 0.0 {
+			cell_size = DEFAULT_CELL_SIZE;
+		}
+		let mut grid = Self::new(cell_size);
+		for (index, wall) in walls.iter().enumerate() {
+			grid.insert(index, wall.p1, wall.p2);
+		}
+		grid
+	}
+
+	pub fn insert(&mut self, index: usize, p1: Point, p2: Point) {
+		if self.is_oversized(p1, p2) {
+			self.oversized.push(index);
+			return;
+		}
+		let cells = &mut self.cells;
+		Self::for_each_cell(self.cell_size, p1, p2, |cell| {
+			cells.entry(cell).or_default().push(index)
+		});
+	}
+
+	pub fn remove(&mut self, index: usize, p1: Point, p2: Point) {
+		if self.is_oversized(p1, p2) {
+			self.oversized.retain(|&wall| wall != index);
+			return;
+		}
+		let cells = &mut self.cells;
+		Self::for_each_cell(self.cell_size, p1, p2, |cell| {
+			if let Some(walls) = cells.get_mut(&cell) {
+				walls.retain(|&wall| wall != index);
+				if walls.is_empty() {
+					cells.remove(&cell);
+				}
+			}
+		});
+	}
+
+	/// Collects the indices of all walls that share at least one cell with the segment between `p1` and `p2`.
+	/// The result is sorted and free of duplicates.
+	pub fn segment_candidates(&self, p1: Point, p2: Point, candidates: &mut Vec<usize>) {
+		candidates.clear();
+		candidates.extend_from_slice(&self.oversized);
+		if self.is_oversized(p1, p2) {
+			// Segments that are too long to be traversed (or can't be located at all) may touch any wall
+			for walls in self.cells.values() {
+				candidates.extend_from_slice(walls);
+			}
+		} else {
+			Self::for_each_cell(self.cell_size, p1, p2, |cell| {
+				if let Some(walls) = self.cells.get(&cell) {
+					candidates.extend_from_slice(walls);
+				}
+			});
+		}
+		candidates.sort_unstable();
+		candidates.dedup();
+	}
+
+	/// Collects the indices of all walls that share at least one cell with the bounding box of the circle.
+	/// The result is sorted and free of duplicates. If the circle covers more cells than there are occupied cells in the grid,
+	/// `false` is returned without collecting anything, because considering all walls will be faster in that case.
+	pub fn circle_candidates(
+		&self,
+		center: Point,
+		radius: f64,
+		candidates: &mut Vec<usize>,
+	) -> bool {
+		candidates.clear();
+		let first_column = Self::cell_coordinate(self.cell_size, center.x - radius);
+		let last_column = Self::cell_coordinate(self.cell_size, center.x + radius);
+		let first_row = Self::cell_coordinate(self.cell_size, center.y - radius);
+		let last_row = Self::cell_coordinate(self.cell_size, center.y + radius);
+		let covered_cells = (last_column as f64 - first_column as f64 + 1.0)
+			* (last_row as f64 - first_row as f64 + 1.0);
+		// This also catches radii that aren't finite
+		if !covered_cells.is_finite() || covered_cells > self.cells.len() as f64 {
+			return false;
+		}
+		candidates.extend_from_slice(&self.oversized);
+		for column in first_column..=last_column {
+			for row in first_row..=last_row {
+				if let Some(walls) = self.cells.get(&(column, row)) {
+					candidates.extend_from_slice(walls);
+				}
+			}
+		}
+		candidates.sort_unstable();
+		candidates.dedup();
+		true
+	}
+
+	fn is_oversized(&self, p1: Point, p2: Point) -> bool {
+		let cells = ((p1.x - p2.x).abs() + (p1.y - p2.y).abs()) / self.cell_size;
+		// Walls with coordinates that aren't finite can't be located in the grid either
+		!cells.is_finite() || cells >= MAX_CELLS_PER_WALL
+	}
+
+	fn cell_coordinate(cell_size: f64, value: f64) -> i32 {
+		(value / cell_size).floor() as i32
+	}
+
+	// Calls `f` for every cell the segment passes through. To be robust against floating point inaccuracies,
+	// cells that the segment only barely misses are reported as well.
+	fn for_each_cell<F: FnMut(CellId)>(cell_size: f64, p1: Point, p2: Point, mut f: F) {
+		let margin = cell_size * 0.000001;
+		let (left, right) = if p1.x <= p2.x { (p1, p2) } else { (p2, p1) };
+		let first_column = Self::cell_coordinate(cell_size, left.x - margin);
+		let last_column = Self::cell_coordinate(cell_size, right.x + margin);
+		for column in first_column..=last_column {
+			let (y1, y2) = if left.x == right.x {
+				(left.y, right.y)
+			} else {
+				let slope = (right.y - left.y) / (right.x - left.x);
+				let x1 = (column as f64 * cell_size - margin).clamp(left.x, right.x);
+				let x2 = ((column + 1) as f64 * cell_size + margin).clamp(left.x, right.x);
+				(
+					left.y + (x1 - left.x) * slope,
+					left.y + (x2 - left.x) * slope,
+				)
+			};
+			let (bottom, top) = if y1 <= y2 { (y1, y2) } else { (y2, y1) };
+			let first_row = Self::cell_coordinate(cell_size, bottom - margin);
+			let last_row = Self::cell_coordinate(cell_size, top + margin);
+			for row in first_row..=last_row {
+				f((column, row));
+			}
+		}
+	}
+}
@@ -0,0 +1,161 @@
+use crate::geometry::Point;
+use crate::raycasting::{Cache, Intersection, TileCache, WallBase, WallGrid, WallHeight};
+use crate::serialization::{
+	decode, deserialize_walls, encode, ids_from_cache, ids_to_indices, parse, serialize_walls,
+	DeserializationError, ParseFailure, ParseResult, Serialize,
+};
+use rustc_hash::FxHashMap;
+
+// 64 bit FNV-1a. Unlike the hashers of the standard library it is guaranteed to stay the same across versions and platforms.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// A hash of the geometry, the heights and the ids of the walls. The intersections and the wall grid only depend on
+/// those, so a cache snapshot stays valid when doors are opened or other properties of the walls change.
+/// The walls are hashed in the order of their ids, because the cache reorders walls when they are removed.
+pub fn wall_set_hash(walls: &[WallBase], wall_ids: &FxHashMap<String, usize>) -> u64 {
+	let mut geometry = wall_ids
+		.iter()
+		.map(|(id, &index)| {
+			let wall = &walls[index];
+			(id.clone(), ((wall.p1, wall.p2), wall.height))
+		})
+		.collect::<Vec<_>>();
+	geometry.sort_unstable_by(|(id1, _), (id2, _)| id1.cmp(id2));
+	let mut data = walls.len().serialize();
+	data.append(&mut geometry.serialize());
+	data.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+		(hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+	})
+}
+
+/// Stores the cache including its intersections and wall grid, so it doesn't need to be built again when the scene is loaded
+pub fn serialize_cache(cache: &Cache) -> String {
+	let mut data = wall_set_hash(&cache.walls, &cache.wall_ids).serialize();
+	data.append(&mut cache.serialize());
+	encode(&data)
+}
+
+/// Restores a cache stored by `serialize_cache` for the given walls and roofs. Returns `None` if the geometry of the
+/// walls differs from the one the cache has been made of or if a wall doesn't have an id. The stored intersections and wall grid are kept, but the
+/// walls and roofs are replaced by the given ones, because their door states and occlusion may have changed since.
+pub fn deserialize_cache(
+	input: &str,
+	walls: Vec<WallBase>,
+	wall_ids: FxHashMap<String, usize>,
+	tiles: TileCache,
+) -> Result<Option<Cache>, DeserializationError> {
+	// Walls without an id can't be matched to the stored ones
+	if wall_ids.len() != walls.len() {
+		return Ok(None);
+	}
+	let (version, input) = decode(input)?;
+	let cache = parse(&input, |input| {
+		let (input, hash) = u64::deserialize(input, version)?;
+		if hash != wall_set_hash(&walls, &wall_ids) {
+			return Ok((input, None));
+		}
+		let (input, cache) = Cache::deserialize(input, version)?;
+		Ok((input, Some(cache)))
+	})?;
+	Ok(cache.and_then(|mut cache| {
+		// The indices of the stored walls are kept, because the intersections and the grid refer to them
+		for (id, &index) in &cache.wall_ids {
+			cache.walls[index] = walls[*wall_ids.get(id)?];
+		}
+		cache.tiles = tiles;
+		Some(cache)
+	}))
+}
+
+impl Serialize for Intersection {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = self.point.serialize();
+		data.append(&mut self.height.serialize());
+		data.append(&mut self.walls.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, point) = Point::deserialize(input, version)?;
+		let (input, height) = WallHeight::deserialize(input, version)?;
+		let (input, walls) = <(usize, usize)>::deserialize(input, version)?;
+		Ok((
+			input,
+			Self {
+				point,
+				height,
+				walls,
+			},
+		))
+	}
+}
+
+// The cells are sorted, so the same grid is always stored the same way
+impl Serialize for WallGrid {
+	fn serialize(&self) -> Vec<u8> {
+		let mut cells = self
+			.cells
+			.iter()
+			.map(|(&cell, walls)| (cell, walls.clone()))
+			.collect::<Vec<_>>();
+		cells.sort_unstable_by_key(|(cell, _)| *cell);
+		let mut data = self.cell_size.serialize();
+		data.append(&mut cells.serialize());
+		data.append(&mut self.oversized.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, cell_size) = f64::deserialize(input, version)?;
+		let (input, cells) = Vec::<((i32, i32), Vec<usize>)>::deserialize(input, version)?;
+		let (input, oversized) = Vec::deserialize(input, version)?;
+		Ok((
+			input,
+			Self {
+				cell_size,
+				cells: cells.into_iter().collect(),
+				oversized,
+			},
+		))
+	}
+}
+
+impl Serialize for Cache {
+	fn serialize(&self) -> Vec<u8> {
+		let (wall_ids, tile_ids) = ids_from_cache(self);
+		let mut data = serialize_walls(&self.walls, &wall_ids, &self.tiles.occluded, &tile_ids);
+		data.append(&mut self.intersections.serialize());
+		data.append(&mut self.grid.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, (walls, wall_ids, roofs, tile_ids)) = deserialize_walls(input, version)?;
+		let (input, intersections) = Vec::<Intersection>::deserialize(input, version)?;
+		let (input, grid) = WallGrid::deserialize(input, version)?;
+
+		// Invalid indices would make the computation panic later on
+		let referenced = intersections
+			.iter()
+			.flat_map(|intersection| [intersection.walls.0, intersection.walls.1])
+			.chain(grid.cells.values().flatten().copied())
+			.chain(grid.oversized.iter().copied());
+		if let Some(index) = referenced.into_iter().find(|&index| index >= walls.len()) {
+			return Err(ParseFailure::invalid_wall_index(index));
+		}
+
+		let mut tiles = TileCache::from_roofs(roofs);
+		tiles.id_map = ids_to_indices(&tile_ids, tile_ids.len());
+		Ok((
+			input,
+			Self {
+				walls,
+				intersections,
+				tiles,
+				wall_ids: ids_to_indices(&wall_ids, wall_ids.len()),
+				grid,
+			},
+		))
+	}
+}
@@ -0,0 +1,174 @@
+use crate::serialization::JsonError;
+use nom::error::{ErrorKind, ParseError};
+use nom::IResult;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The reasons why serialized data couldn't be read.
+/// Offsets count the bytes of the decompressed data, which follows the version byte.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeserializationError {
+	/// The input contains characters that aren't part of the ascii85 alphabet
+	InvalidAscii85 { message: String },
+	/// The input doesn't contain any data, so not even the version is known
+	Empty,
+	/// The data has been written by a newer version of the module
+	UnsupportedVersion { version: u8 },
+	/// The compressed data is corrupt. This usually means that the input has been cut off.
+	InvalidCompression { message: String },
+	/// The data ends in the middle of a value
+	UnexpectedEnd {
+		offset: usize,
+		expected: &'static str,
+	},
+	/// A byte doesn't encode any of the values that are valid at its position
+	InvalidValue {
+		offset: usize,
+		value: u8,
+		expected: &'static str,
+	},
+	/// A wall refers to a roof that isn't part of the data
+	InvalidRoof { wall: usize, roof: usize },
+	/// An intersection or a cell of the wall grid refers to a wall that isn't part of the data
+	InvalidWallIndex { index: usize },
+	/// The input looks like JSON, but couldn't be read as such
+	InvalidJson(JsonError),
+}
+
+impl Display for DeserializationError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidAscii85 { message } => {
+				write!(f, "The input isn't valid ascii85: {}", message)
+			}
+			Self::Empty => write!(f, "The input is empty"),
+			Self::UnsupportedVersion { version } => write!(
+				f,
+				"The data has version {}, but only versions up to {} are supported",
+				version,
+				super::CURRENT_VERSION
+			),
+			Self::InvalidCompression { message } => write!(
+				f,
+				"The data couldn't be decompressed, it might be incomplete: {}",
+				message
+			),
+			Self::UnexpectedEnd { offset, expected } => write!(
+				f,
+				"The data ends at byte {} while reading a {}, it might be incomplete",
+				offset, expected
+			),
+			Self::InvalidValue {
+				offset,
+				value,
+				expected,
+			} => write!(
+				f,
+				"Byte {} has the value {}, which isn't a valid {}",
+				offset, value, expected
+			),
+			Self::InvalidRoof { wall, roof } => write!(
+				f,
+				"Wall {} belongs to roof {}, which doesn't exist",
+				wall, roof
+			),
+			Self::InvalidWallIndex { index } => {
+				write!(f, "Wall {} is referenced, but doesn't exist", index)
+			}
+			Self::InvalidJson(error) => write!(f, "The input isn't valid JSON: {}", error),
+		}
+	}
+}
+
+impl Error for DeserializationError {}
+
+impl From<JsonError> for DeserializationError {
+	fn from(error: JsonError) -> Self {
+		Self::InvalidJson(error)
+	}
+}
+
+pub type ParseResult<'a, T> = IResult<&'a [u8], T, ParseFailure>;
+
+/// The error of the parsers. Instead of an offset it stores how many bytes were left when parsing failed,
+/// because the parsers only ever see the remaining input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseFailure {
+	remaining: usize,
+	kind: FailureKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FailureKind {
+	UnexpectedEnd { expected: &'static str },
+	InvalidValue { value: u8, expected: &'static str },
+	InvalidRoof { wall: usize, roof: usize },
+	InvalidWallIndex { index: usize },
+}
+
+impl ParseFailure {
+	pub fn unexpected_end(input: &[u8], expected: &'static str) -> nom::Err<Self> {
+		nom::Err::Failure(Self {
+			remaining: input.len(),
+			kind: FailureKind::UnexpectedEnd { expected },
+		})
+	}
+
+	/// `input` needs to start with the invalid byte
+	pub fn invalid_value(input: &[u8], expected: &'static str) -> nom::Err<Self> {
+		nom::Err::Failure(Self {
+			remaining: input.len(),
+			kind: FailureKind::InvalidValue {
+				value: input[0],
+				expected,
+			},
+		})
+	}
+
+	pub fn invalid_roof(wall: usize, roof: usize) -> nom::Err<Self> {
+		nom::Err::Failure(Self {
+			remaining: 0,
+			kind: FailureKind::InvalidRoof { wall, roof },
+		})
+	}
+
+	pub fn invalid_wall_index(index: usize) -> nom::Err<Self> {
+		nom::Err::Failure(Self {
+			remaining: 0,
+			kind: FailureKind::InvalidWallIndex { index },
+		})
+	}
+
+	pub fn into_error(self, total_length: usize) -> DeserializationError {
+		let offset = total_length - self.remaining;
+		match self.kind {
+			FailureKind::UnexpectedEnd { expected } => {
+				DeserializationError::UnexpectedEnd { offset, expected }
+			}
+			FailureKind::InvalidValue { value, expected } => DeserializationError::InvalidValue {
+				offset,
+				value,
+				expected,
+			},
+			FailureKind::InvalidRoof { wall, roof } => {
+				DeserializationError::InvalidRoof { wall, roof }
+			}
+			FailureKind::InvalidWallIndex { index } => {
+				DeserializationError::InvalidWallIndex { index }
+			}
+		}
+	}
+}
+
+impl ParseError<&[u8]> for ParseFailure {
+	fn from_error_kind(input: &[u8], _kind: ErrorKind) -> Self {
+		Self {
+			remaining: input.len(),
+			kind: FailureKind::UnexpectedEnd { expected: "value" },
+		}
+	}
+
+	fn append(_input: &[u8], _kind: ErrorKind, other: Self) -> Self {
+		other
+	}
+}
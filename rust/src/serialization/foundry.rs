@@ -0,0 +1,181 @@
+use crate::geometry::Point;
+use crate::raycasting::*;
+use crate::serialization::json::{as_object, field, optional_field};
+use crate::serialization::JsonError;
+use rustc_hash::FxHashMap;
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+
+// `CONST.TILE_OCCLUSION_MODES.ROOF`
+const ROOF_OCCLUSION_MODE: usize = 2;
+
+/// Builds the cache of a scene exported from Foundry v0.7 or v0.8 using "Export Data", the same way `buildCache`
+/// builds it from the walls on the canvas. That way reported maps can be reproduced without loading them in Foundry.
+///
+/// Foundry decides which roof a wall belongs to using the pixels of the tile's texture, which aren't part of the
+/// export. Instead a wall belongs to the first overhead roof tile whose rectangle contains both of its endpoints.
+/// Roofs are imported as not occluded, which is their state while no token is below them.
+pub fn import_foundry_scene(input: &str, enable_height: bool) -> Result<Cache, JsonError> {
+	let value = serde_json::from_str(input).map_err(|error| JsonError::new(error.to_string()))?;
+	let scene = as_object(&value)?;
+	let roofs = match scene.get("tiles") {
+		Some(tiles) => read_list(tiles, "tiles", read_roof)?
+			.into_iter()
+			.flatten()
+			.collect(),
+		None => vec![],
+	};
+	let walls = read_list(
+		scene
+			.get("walls")
+			.ok_or_else(|| JsonError::new("Missing field `walls`"))?,
+		"walls",
+		|wall| read_wall(wall, enable_height),
+	)?;
+
+	let mut tiles = TileCache::default();
+	let mut wall_ids = FxHashMap::default();
+	let mut wall_bases = Vec::with_capacity(walls.len());
+	for (id, mut wall) in walls {
+		wall.roof = roofs
+			.iter()
+			.find(|roof| roof.contains(wall.p1) && roof.contains(wall.p2))
+			.map(|roof| tiles.get_or_insert(roof.id.clone(), false));
+		wall_ids.insert(id, wall_bases.len());
+		wall_bases.push(wall);
+	}
+	let mut cache = Cache::build(wall_bases, tiles);
+	cache.wall_ids = wall_ids;
+	Ok(cache)
+}
+
+fn read_list<T, F: FnMut(&Map<String, Value>) -> Result<T, JsonError>>(
+	value: &Value,
+	name: &str,
+	mut read: F,
+) -> Result<Vec<T>, JsonError> {
+	value
+		.as_array()
+		.ok_or_else(|| JsonError::new("Expected an array").inside(name))?
+		.iter()
+		.enumerate()
+		.map(|(i, entry)| {
+			as_object(entry)
+				.and_then(&mut read)
+				.map_err(|error| error.inside(&format!("[{}]", i)).inside(name))
+		})
+		.collect()
+}
+
+fn read_wall(
+	wall: &Map<String, Value>,
+	enable_height: bool,
+) -> Result<(String, WallBase), JsonError> {
+	let id = field::<String>(wall, "_id")?;
+	let c = field::<Vec<f64>>(wall, "c")?;
+	if c.len() != 4 {
+		return Err(JsonError::new("Expected 4 coordinates").inside("c"));
+	}
+	let sense = enum_field(wall, "sense", WallSenseType::NORMAL)?;
+	let height = if enable_height {
+		read_height(wall)?
+	} else {
+		WallHeight::default()
+	};
+	let wall = WallBase::new(
+		Point::new(c[0].round(), c[1].round()),
+		Point::new(c[2].round(), c[3].round()),
+		enum_field(wall, "move", WallSenseType::NORMAL)?,
+		sense,
+		// Foundry v0.7 doesn't know sound restrictions, sound was blocked like sight
+		enum_field(wall, "sound", sense)?,
+		enum_field(wall, "door", DoorType::NONE)?,
+		enum_field(wall, "ds", DoorState::CLOSED)?,
+		enum_field(wall, "dir", WallDirection::BOTH)?,
+		height,
+		None,
+	);
+	Ok((id, wall))
+}
+
+// The height set by the Wall Height module
+fn read_height(wall: &Map<String, Value>) -> Result<WallHeight, JsonError> {
+	let height = wall
+		.get("flags")
+		.and_then(|flags| flags.get("wallHeight"))
+		.and_then(Value::as_object);
+	let height = match height {
+		Some(height) => height,
+		None => return Ok(WallHeight::default()),
+	};
+	let error_path = |error: JsonError| error.inside("wallHeight").inside("flags");
+	let default = WallHeight::default();
+	Ok(WallHeight {
+		top: optional_field(height, "wallHeightTop")
+			.map_err(error_path)?
+			.unwrap_or(default.top),
+		bottom: optional_field(height, "wallHeightBottom")
+			.map_err(error_path)?
+			.unwrap_or(default.bottom),
+	})
+}
+
+// Enums are stored as numbers in Foundry's data. Fields that are missing in older versions get a default.
+fn enum_field<T: TryFrom<usize>>(
+	object: &Map<String, Value>,
+	name: &str,
+	default: T,
+) -> Result<T, JsonError> {
+	match optional_field::<usize>(object, name)? {
+		Some(value) => T::try_from(value)
+			.map_err(|_| JsonError::new(format!("{} isn't a valid value", value)).inside(name)),
+		None => Ok(default),
+	}
+}
+
+struct Roof {
+	id: String,
+	center: Point,
+	half_width: f64,
+	half_height: f64,
+	/// In radians
+	rotation: f64,
+}
+
+impl Roof {
+	fn contains(&self, point: Point) -> bool {
+		// Rotate the point into the coordinate system of the tile
+		let (sin, cos) = (-self.rotation).sin_cos();
+		let dx = point.x - self.center.x;
+		let dy = point.y - self.center.y;
+		let x = dx * cos - dy * sin;
+		let y = dx * sin + dy * cos;
+		x.abs() <= self.half_width && y.abs() <= self.half_height
+	}
+}
+
+// Returns `None` for tiles that aren't roofs
+fn read_roof(tile: &Map<String, Value>) -> Result<Option<Roof>, JsonError> {
+	let overhead = optional_field::<bool>(tile, "overhead")?.unwrap_or(false);
+	let occlusion_mode = match tile.get("occlusion").and_then(Value::as_object) {
+		Some(occlusion) => {
+			optional_field::<usize>(occlusion, "mode").map_err(|error| error.inside("occlusion"))?
+		}
+		None => None,
+	};
+	if !overhead || occlusion_mode != Some(ROOF_OCCLUSION_MODE) {
+		return Ok(None);
+	}
+	let x = field::<f64>(tile, "x")?;
+	let y = field::<f64>(tile, "y")?;
+	let width = field::<f64>(tile, "width")?;
+	let height = field::<f64>(tile, "height")?;
+	let rotation = optional_field::<f64>(tile, "rotation")?.unwrap_or(0.0);
+	Ok(Some(Roof {
+		id: field(tile, "_id")?,
+		center: Point::new(x + width / 2.0, y + height / 2.0),
+		half_width: width.abs() / 2.0,
+		half_height: height.abs() / 2.0,
+		rotation: rotation.to_radians(),
+	}))
+}
@@ -0,0 +1,185 @@
+use crate::geometry::Point;
+use crate::minimize::{minimize, Failure};
+use crate::raycasting::js_api::{log, walls_from_js, JsPoint};
+use crate::raycasting::*;
+use crate::serialization::{
+	ascii85_to_json, deserialize_ascii85, deserialize_cache, serialize_ascii85, serialize_cache,
+	DeserializationError, RaycastingCall, SceneSnapshot, SceneTestCase, TestCase,
+};
+use js_sys::{Array, Object};
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+impl From<RaycastingCall> for Object {
+	fn from(value: RaycastingCall) -> Self {
+		use js_sys::Reflect::set;
+		let result = Object::new();
+		set(
+			&result,
+			&JsValue::from_str("walls"),
+			&value
+				.walls
+				.into_iter()
+				.map::<JsValue, _>(|wall| wall.into())
+				.collect::<Array>(),
+		)
+		.unwrap();
+		set(&result, &JsValue::from_str("origin"), &value.origin.into()).unwrap();
+		set(&result, &JsValue::from_str("height"), &value.height.into()).unwrap();
+		set(&result, &JsValue::from_str("radius"), &value.radius.into()).unwrap();
+		set(
+			&result,
+			&JsValue::from_str("distance"),
+			&value.distance.into(),
+		)
+		.unwrap();
+		set(
+			&result,
+			&JsValue::from_str("density"),
+			&value.density.into(),
+		)
+		.unwrap();
+		set(&result, &JsValue::from_str("angle"), &value.angle.into()).unwrap();
+		set(
+			&result,
+			&JsValue::from_str("rotation"),
+			&value.rotation.into(),
+		)
+		.unwrap();
+		result
+	}
+}
+
+#[wasm_bindgen(js_name=serializeData)]
+#[allow(dead_code)]
+pub fn js_serialize_data(
+	cache: &Cache,
+	origin: JsPoint,
+	height: f64,
+	radius: f64,
+	distance: f64,
+	density: f64,
+	angle: f64,
+	rotation: f64,
+	polygon_type: &str,
+) -> String {
+	let source = PolygonSource {
+		origin: Point::from(&origin.into()),
+		height,
+		radius,
+		distance,
+		density,
+		angle,
+		rotation,
+		polygon_type: PolygonType::from(polygon_type),
+	};
+	serialize_ascii85(RaycastingCall::new(cache, &source))
+}
+
+#[wasm_bindgen(js_name=deserializeData)]
+#[allow(dead_code)]
+pub fn js_deserialize_data(str: &str) -> Result<Object, JsValue> {
+	let data = deserialize_ascii85::<RaycastingCall>(str)?;
+	Ok(data.into())
+}
+
+// Converts the output of `serializeData` into JSON, which is easier to read and edit
+#[wasm_bindgen(js_name=dataToJson)]
+#[allow(dead_code)]
+pub fn js_data_to_json(str: &str) -> Result<String, JsValue> {
+	Ok(ascii85_to_json::<RaycastingCall>(str)?)
+}
+
+#[wasm_bindgen(js_name=generateTest)]
+#[allow(dead_code)]
+pub fn js_generate_test(str: &str) -> Result<String, JsValue> {
+	let data = deserialize_ascii85::<RaycastingCall>(str)?;
+	let cache = Cache::build(
+		data.walls.clone(),
+		TileCache::from_roofs(data.roofs.clone()),
+	);
+	let (los, fov) = compute_polygon(
+		&cache,
+		data.origin,
+		data.height,
+		data.radius,
+		data.distance,
+		data.density,
+		VisionAngle::from_rotation_and_angle(data.rotation, data.angle, data.origin),
+		data.polygon_type,
+		None,
+	)?;
+	Ok(serialize_ascii85(TestCase {
+		call: data,
+		los,
+		fov,
+	}))
+}
+
+// Shrinks the output of `serializeData` to the walls and roofs that are needed to reproduce the failure. If `expected`
+// is the output of `generateTest` from a version that computes the polygons correctly, any other result counts as a
+// failure. Otherwise only errors and invalid polygons do. Returns `undefined` if the call doesn't fail.
+// Panics can't be caught in the browser, calls that panic need to be minimized with the `minimize` command of the
+// profiling binary instead.
+#[wasm_bindgen(js_name=minimizeData)]
+#[allow(dead_code)]
+pub fn js_minimize_data(str: &str, expected: Option<String>) -> Result<Option<String>, JsValue> {
+	let data = deserialize_ascii85::<RaycastingCall>(str)?;
+	let failure = match expected {
+		Some(expected) => {
+			let expected = deserialize_ascii85::<TestCase>(&expected)?;
+			Failure::Mismatch {
+				los: expected.los,
+				fov: expected.fov,
+			}
+		}
+		None => Failure::Error,
+	};
+	Ok(minimize(data, &failure).map(serialize_ascii85))
+}
+
+// Like `serializeData`, but for all sources of the scene at once
+#[wasm_bindgen(js_name=serializeScene)]
+#[allow(dead_code)]
+pub fn js_serialize_scene(cache: &Cache, js_sources: Vec<JsValue>) -> Result<String, JsValue> {
+	let sources = js_sources
+		.into_iter()
+		.map(|source| PolygonSource::try_from(&source.into()))
+		.collect::<Result<_, _>>()?;
+	Ok(serialize_ascii85(SceneSnapshot::new(cache, sources)))
+}
+
+#[wasm_bindgen(js_name=generateSceneTest)]
+#[allow(dead_code)]
+pub fn js_generate_scene_test(str: &str) -> Result<String, JsValue> {
+	let scene = deserialize_ascii85::<SceneSnapshot>(str)?;
+	Ok(serialize_ascii85(SceneTestCase::generate(scene)?))
+}
+
+// Stores the cache, so `loadCache` can restore it without building it again
+#[wasm_bindgen(js_name=serializeCache)]
+#[allow(dead_code)]
+pub fn js_serialize_cache(cache: &Cache) -> String {
+	serialize_cache(cache)
+}
+
+// Restores a cache stored by `serializeCache`. Returns `undefined` if the geometry of the walls on the canvas differs
+// from the one the cache has been built of, or if the stored cache can't be read. The cache needs to be built in that case.
+#[wasm_bindgen(js_name=loadCache)]
+#[allow(dead_code)]
+pub fn js_load_cache(str: &str, js_walls: Vec<JsValue>, enable_height: bool) -> Option<Cache> {
+	let (walls, wall_ids, tiles) = walls_from_js(js_walls, enable_height);
+	deserialize_cache(str, walls, wall_ids, tiles).unwrap_or_else(|error| {
+		log(&format!(
+			"Lichtgeschwindigkeit | The stored cache couldn't be loaded: {}",
+			error
+		));
+		None
+	})
+}
+
+impl From<DeserializationError> for JsValue {
+	fn from(error: DeserializationError) -> Self {
+		js_sys::Error::new(&format!("Lichtgeschwindigkeit | {}", error)).into()
+	}
+}
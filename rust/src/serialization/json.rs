@@ -0,0 +1,484 @@
+use crate::geometry::Point;
+use crate::raycasting::*;
+use crate::serialization::{
+	invalid_roof, with_ids, RaycastingCall, SceneSnapshot, SceneTestCase, TestCase, WallsAndRoofs,
+};
+use serde_json::{Map, Value};
+use std::fmt::{self, Display, Formatter};
+
+/// A readable alternative to the ascii85 format that can be reviewed and edited by hand
+pub trait Json {
+	fn to_json(&self) -> Value;
+	fn from_json(value: &Value) -> Result<Self, JsonError>
+	where
+		Self: Sized;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+	/// Where in the document the error occured, for example `walls[3].height.top`
+	pub path: String,
+	pub message: String,
+}
+
+impl JsonError {
+	pub(super) fn new(message: impl Into<String>) -> Self {
+		Self {
+			path: String::new(),
+			message: message.into(),
+		}
+	}
+
+	// Prepends the name of the field or index that contains the erroneous value
+	pub(super) fn inside(mut self, segment: &str) -> Self {
+		if !self.path.is_empty() && !self.path.starts_with('[') {
+			self.path.insert(0, '.');
+		}
+		self.path.insert_str(0, segment);
+		self
+	}
+}
+
+impl Display for JsonError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if self.path.is_empty() {
+			write!(f, "{}", self.message)
+		} else {
+			write!(f, "{}: {}", self.path, self.message)
+		}
+	}
+}
+
+impl std::error::Error for JsonError {}
+
+pub fn serialize_json<T: Json>(data: &T) -> String {
+	serde_json::to_string_pretty(&data.to_json()).unwrap()
+}
+
+pub fn deserialize_json<T: Json>(input: &str) -> Result<T, JsonError> {
+	let value = serde_json::from_str(input).map_err(|error| JsonError::new(error.to_string()))?;
+	T::from_json(&value)
+}
+
+pub(super) fn as_object(value: &Value) -> Result<&Map<String, Value>, JsonError> {
+	value
+		.as_object()
+		.ok_or_else(|| JsonError::new("Expected an object"))
+}
+
+pub(super) fn field<T: Json>(object: &Map<String, Value>, name: &str) -> Result<T, JsonError> {
+	let value = object
+		.get(name)
+		.ok_or_else(|| JsonError::new(format!("Missing field `{}`", name)))?;
+	T::from_json(value).map_err(|error| error.inside(name))
+}
+
+// Fields that were added later may be missing
+pub(super) fn optional_field<T: Json>(
+	object: &Map<String, Value>,
+	name: &str,
+) -> Result<Option<T>, JsonError> {
+	match object.get(name) {
+		Some(value) => Option::<T>::from_json(value).map_err(|error| error.inside(name)),
+		None => Ok(None),
+	}
+}
+
+// JSON numbers can't be infinite, but wall heights usually are. Non-finite numbers are therefore written as strings.
+// Finite numbers are written with the shortest representation that parses to the same value, so they round-trip exactly.
+impl Json for f64 {
+	fn to_json(&self) -> Value {
+		if self.is_finite() {
+			Value::from(*self)
+		} else if self.is_nan() {
+			Value::from("NaN")
+		} else if *self > 0.0 {
+			Value::from("Infinity")
+		} else {
+			Value::from("-Infinity")
+		}
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		match value {
+			Value::Number(number) => number
+				.as_f64()
+				.ok_or_else(|| JsonError::new("Expected a number")),
+			Value::String(string) if string == "NaN" => Ok(f64::NAN),
+			Value::String(string) if string == "Infinity" => Ok(f64::INFINITY),
+			Value::String(string) if string == "-Infinity" => Ok(f64::NEG_INFINITY),
+			_ => Err(JsonError::new("Expected a number")),
+		}
+	}
+}
+
+impl Json for bool {
+	fn to_json(&self) -> Value {
+		Value::from(*self)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		value
+			.as_bool()
+			.ok_or_else(|| JsonError::new("Expected a boolean"))
+	}
+}
+
+impl Json for usize {
+	fn to_json(&self) -> Value {
+		Value::from(*self)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		value
+			.as_u64()
+			.map(|value| value as usize)
+			.ok_or_else(|| JsonError::new("Expected an unsigned integer"))
+	}
+}
+
+impl Json for String {
+	fn to_json(&self) -> Value {
+		Value::from(self.as_str())
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		value
+			.as_str()
+			.map(str::to_owned)
+			.ok_or_else(|| JsonError::new("Expected a string"))
+	}
+}
+
+impl<T: Json> Json for Vec<T> {
+	fn to_json(&self) -> Value {
+		self.iter().map(Json::to_json).collect()
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		value
+			.as_array()
+			.ok_or_else(|| JsonError::new("Expected an array"))?
+			.iter()
+			.enumerate()
+			.map(|(i, entry)| {
+				T::from_json(entry).map_err(|error| error.inside(&format!("[{}]", i)))
+			})
+			.collect()
+	}
+}
+
+impl<T: Json> Json for Option<T> {
+	fn to_json(&self) -> Value {
+		match self {
+			Some(value) => value.to_json(),
+			None => Value::Null,
+		}
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		match value {
+			Value::Null => Ok(None),
+			value => T::from_json(value).map(Some),
+		}
+	}
+}
+
+impl Json for Point {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		object.insert("x".into(), self.x.to_json());
+		object.insert("y".into(), self.y.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		Ok(Self::new(field(object, "x")?, field(object, "y")?))
+	}
+}
+
+impl Json for WallHeight {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		object.insert("top".into(), self.top.to_json());
+		object.insert("bottom".into(), self.bottom.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		Ok(Self {
+			top: field(object, "top")?,
+			bottom: field(object, "bottom")?,
+		})
+	}
+}
+
+// Enums are written as the names of their variants
+macro_rules! ImplJsonForEnum (
+	($name:ident, [$($variant:ident),*]) => {
+		impl Json for $name {
+			fn to_json(&self) -> Value {
+				let name = match self {
+					$(Self::$variant => stringify!($variant),)*
+				};
+				Value::from(name)
+			}
+
+			fn from_json(value: &Value) -> Result<Self, JsonError> {
+				match value.as_str() {
+					$(Some(stringify!($variant)) => Ok(Self::$variant),)*
+					_ => Err(JsonError::new(concat!(
+						"Expected one of"$(, " ", stringify!($variant))*
+					))),
+				}
+			}
+		}
+	};
+);
+
+ImplJsonForEnum!(WallSenseType, [NONE, NORMAL, LIMITED]);
+ImplJsonForEnum!(DoorType, [NONE, DOOR, SECRET]);
+ImplJsonForEnum!(DoorState, [CLOSED, OPEN, LOCKED]);
+ImplJsonForEnum!(WallDirection, [BOTH, LEFT, RIGHT]);
+ImplJsonForEnum!(PolygonType, [SIGHT, SOUND, LIGHT, MOVEMENT]);
+
+impl Json for WallBase {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		object.insert("p1".into(), self.p1.to_json());
+		object.insert("p2".into(), self.p2.to_json());
+		object.insert("movement".into(), self.movement.to_json());
+		object.insert("sense".into(), self.sense.to_json());
+		object.insert("sound".into(), self.sound.to_json());
+		object.insert("door".into(), self.door.to_json());
+		object.insert("ds".into(), self.ds.to_json());
+		object.insert("dir".into(), self.dir.to_json());
+		object.insert("height".into(), self.height.to_json());
+		object.insert("roof".into(), self.roof.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		Ok(Self {
+			p1: field(object, "p1")?,
+			p2: field(object, "p2")?,
+			movement: field(object, "movement")?,
+			sense: field(object, "sense")?,
+			sound: field(object, "sound")?,
+			door: field(object, "door")?,
+			ds: field(object, "ds")?,
+			dir: field(object, "dir")?,
+			height: field(object, "height")?,
+			roof: field(object, "roof")?,
+		})
+	}
+}
+
+// Wall ids are written into the walls, so walls can be found in the scene while reading the JSON
+fn insert_walls(
+	object: &mut Map<String, Value>,
+	walls: &[WallBase],
+	wall_ids: &[Option<String>],
+	roofs: &[bool],
+	tile_ids: &[Option<String>],
+) {
+	let walls = with_ids(walls, wall_ids)
+		.into_iter()
+		.map(|(wall, id)| {
+			let mut wall = wall.to_json();
+			if let (Some(id), Value::Object(object)) = (id, &mut wall) {
+				object.insert("id".into(), id.to_json());
+			}
+			wall
+		})
+		.collect();
+	let (roofs, tile_ids): (Vec<_>, Vec<_>) = with_ids(roofs, tile_ids)
+		.into_iter()
+		.map(|(roof, id)| (roof.to_json(), id.to_json()))
+		.unzip();
+	object.insert("walls".into(), walls);
+	object.insert("roofs".into(), roofs.into());
+	object.insert("tile_ids".into(), tile_ids.into());
+}
+
+fn walls_from_json(object: &Map<String, Value>) -> Result<WallsAndRoofs, JsonError> {
+	let walls: Vec<WallBase> = field(object, "walls")?;
+	// Parsing the walls succeeded, so they are an array of objects
+	let wall_ids = object["walls"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.enumerate()
+		.map(|(i, wall)| {
+			optional_field(as_object(wall).unwrap(), "id")
+				.map_err(|error| error.inside(&format!("[{}]", i)).inside("walls"))
+		})
+		.collect::<Result<_, _>>()?;
+	let roofs: Vec<bool> = field(object, "roofs")?;
+	let tile_ids = optional_field::<Vec<Option<String>>>(object, "tile_ids")?
+		.unwrap_or_else(|| vec![None; roofs.len()]);
+	if tile_ids.len() != roofs.len() {
+		return Err(JsonError::new(format!(
+			"Expected {} entries, one for every roof",
+			roofs.len()
+		))
+		.inside("tile_ids"));
+	}
+	if let Some((wall, roof)) = invalid_roof(&walls, &roofs) {
+		return Err(JsonError::new(format!("Roof {} doesn't exist", roof))
+			.inside("roof")
+			.inside(&format!("[{}]", wall))
+			.inside("walls"));
+	}
+	Ok((walls, wall_ids, roofs, tile_ids))
+}
+
+impl Json for RaycastingCall {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		insert_walls(
+			&mut object,
+			&self.walls,
+			&self.wall_ids,
+			&self.roofs,
+			&self.tile_ids,
+		);
+		object.insert("origin".into(), self.origin.to_json());
+		object.insert("height".into(), self.height.to_json());
+		object.insert("radius".into(), self.radius.to_json());
+		object.insert("distance".into(), self.distance.to_json());
+		object.insert("density".into(), self.density.to_json());
+		object.insert("angle".into(), self.angle.to_json());
+		object.insert("rotation".into(), self.rotation.to_json());
+		object.insert("polygon_type".into(), self.polygon_type.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		let (walls, wall_ids, roofs, tile_ids) = walls_from_json(object)?;
+		Ok(Self {
+			walls,
+			wall_ids,
+			roofs,
+			tile_ids,
+			origin: field(object, "origin")?,
+			height: field(object, "height")?,
+			radius: field(object, "radius")?,
+			distance: field(object, "distance")?,
+			density: field(object, "density")?,
+			angle: field(object, "angle")?,
+			rotation: field(object, "rotation")?,
+			polygon_type: field(object, "polygon_type")?,
+		})
+	}
+}
+
+impl Json for PolygonSource {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		object.insert("origin".into(), self.origin.to_json());
+		object.insert("height".into(), self.height.to_json());
+		object.insert("radius".into(), self.radius.to_json());
+		object.insert("distance".into(), self.distance.to_json());
+		object.insert("density".into(), self.density.to_json());
+		object.insert("angle".into(), self.angle.to_json());
+		object.insert("rotation".into(), self.rotation.to_json());
+		object.insert("polygon_type".into(), self.polygon_type.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		Ok(Self {
+			origin: field(object, "origin")?,
+			height: field(object, "height")?,
+			radius: field(object, "radius")?,
+			distance: field(object, "distance")?,
+			density: field(object, "density")?,
+			angle: field(object, "angle")?,
+			rotation: field(object, "rotation")?,
+			polygon_type: field(object, "polygon_type")?,
+		})
+	}
+}
+
+impl Json for SceneSnapshot {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		insert_walls(
+			&mut object,
+			&self.walls,
+			&self.wall_ids,
+			&self.roofs,
+			&self.tile_ids,
+		);
+		object.insert("sources".into(), self.sources.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		let (walls, wall_ids, roofs, tile_ids) = walls_from_json(object)?;
+		Ok(Self {
+			walls,
+			wall_ids,
+			roofs,
+			tile_ids,
+			sources: field(object, "sources")?,
+		})
+	}
+}
+
+impl Json for TestCase {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		object.insert("call".into(), self.call.to_json());
+		object.insert("los".into(), self.los.to_json());
+		object.insert("fov".into(), self.fov.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		Ok(Self {
+			call: field(object, "call")?,
+			los: field(object, "los")?,
+			fov: field(object, "fov")?,
+		})
+	}
+}
+
+impl Json for (Vec<Point>, Vec<Point>) {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		object.insert("los".into(), self.0.to_json());
+		object.insert("fov".into(), self.1.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		Ok((field(object, "los")?, field(object, "fov")?))
+	}
+}
+
+impl Json for SceneTestCase {
+	fn to_json(&self) -> Value {
+		let mut object = Map::new();
+		object.insert("scene".into(), self.scene.to_json());
+		object.insert("polygons".into(), self.polygons.to_json());
+		Value::Object(object)
+	}
+
+	fn from_json(value: &Value) -> Result<Self, JsonError> {
+		let object = as_object(value)?;
+		Ok(Self {
+			scene: field(object, "scene")?,
+			polygons: field(object, "polygons")?,
+		})
+	}
+}
@@ -0,0 +1,810 @@
+mod cache;
+mod error;
+mod foundry;
+#[cfg(feature = "wasm")]
+mod js_api;
+mod json;
+
+use crate::geometry::Point;
+use crate::raycasting::*;
+use rustc_hash::FxHashMap;
+use std::convert::{TryFrom, TryInto};
+use std::mem::size_of;
+use yazi::{compress, decompress, CompressionLevel, Format};
+
+pub use cache::{deserialize_cache, serialize_cache, wall_set_hash};
+pub use error::{DeserializationError, ParseFailure, ParseResult};
+pub use foundry::import_foundry_scene;
+pub use json::{deserialize_json, serialize_json, Json, JsonError};
+
+const CURRENT_VERSION: u8 = 4;
+
+pub trait Serialize {
+	fn serialize(&self) -> Vec<u8>;
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self>
+	where
+		Self: Sized;
+}
+
+pub trait SerializeByte {
+	fn serialize_byte(&self) -> u8;
+	fn deserialize_byte(input: &[u8]) -> ParseResult<'_, Self>
+	where
+		Self: Sized;
+}
+
+impl<T: SerializeByte> Serialize for T
+where
+	Self: Sized,
+{
+	fn serialize(&self) -> Vec<u8> {
+		vec![self.serialize_byte()]
+	}
+
+	fn deserialize(input: &[u8], _version: u8) -> ParseResult<'_, Self> {
+		Self::deserialize_byte(input)
+	}
+}
+
+// Like nom's `take`, but the error says what was being read
+fn take_bytes<'a>(
+	input: &'a [u8],
+	count: usize,
+	expected: &'static str,
+) -> ParseResult<'a, &'a [u8]> {
+	if input.len() < count {
+		return Err(ParseFailure::unexpected_end(input, expected));
+	}
+	Ok((&input[count..], &input[..count]))
+}
+
+macro_rules! ImplSerializeByteForEnum (
+	($name:ident) => {
+		impl SerializeByte for $name {
+			fn serialize_byte(&self) -> u8 {
+				// TODO Try into would be better here
+				*self as u8
+			}
+
+			fn deserialize_byte(input: &[u8]) -> ParseResult<'_, Self> {
+				let (rest, byte) = take_bytes(input, 1, stringify!($name))?;
+				let value = Self::try_from(byte[0] as usize)
+					.map_err(|_| ParseFailure::invalid_value(input, stringify!($name)))?;
+				Ok((rest, value))
+			}
+		}
+	};
+);
+
+macro_rules! ImplSerializeForNumber (
+	($name:ident, $expected:expr) => {
+		impl Serialize for $name {
+			fn serialize(&self) -> Vec<u8> {
+				self.to_be_bytes().into()
+			}
+
+			fn deserialize(input: &[u8], _version: u8) -> ParseResult<'_, Self> {
+				let (input, representation) = take_bytes(input, size_of::<Self>(), $expected)?;
+				Ok((
+					input,
+					Self::from_be_bytes(representation.try_into().unwrap()),
+				))
+			}
+		}
+	};
+);
+
+ImplSerializeForNumber!(f64, "number");
+ImplSerializeForNumber!(u32, "whole number");
+ImplSerializeForNumber!(u64, "whole number");
+ImplSerializeForNumber!(i32, "whole number");
+
+impl Serialize for usize {
+	fn serialize(&self) -> Vec<u8> {
+		u32::try_from(*self).unwrap().serialize()
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, value) = u32::deserialize(input, version)?;
+		Ok((input, value.try_into().unwrap()))
+	}
+}
+
+impl Serialize for String {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = self.len().serialize();
+		data.extend_from_slice(self.as_bytes());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (rest, len) = usize::deserialize(input, version)?;
+		let (rest, bytes) = take_bytes(rest, len, "string")?;
+		let string = String::from_utf8(bytes.to_vec())
+			.map_err(|_| ParseFailure::invalid_value(&input[size_of::<u32>()..], "UTF-8 string"))?;
+		Ok((rest, string))
+	}
+}
+
+impl<A: Serialize, B: Serialize> Serialize for (A, B) {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = self.0.serialize();
+		data.append(&mut self.1.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, a) = A::deserialize(input, version)?;
+		let (input, b) = B::deserialize(input, version)?;
+		Ok((input, (a, b)))
+	}
+}
+
+impl Serialize for Point {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = Vec::with_capacity(size_of::<Self>());
+		data.append(&mut self.x.serialize());
+		data.append(&mut self.y.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, x) = f64::deserialize(input, version)?;
+		let (input, y) = f64::deserialize(input, version)?;
+		Ok((input, Self { x, y }))
+	}
+}
+
+impl Serialize for WallHeight {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = Vec::with_capacity(size_of::<Self>());
+		data.append(&mut self.top.serialize());
+		data.append(&mut self.bottom.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, top) = f64::deserialize(input, version)?;
+		let (input, bottom) = f64::deserialize(input, version)?;
+		Ok((input, Self { top, bottom }))
+	}
+}
+
+impl<T: Serialize> Serialize for Vec<T> {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = Vec::new();
+		data.append(&mut u32::try_from(self.len()).unwrap().to_be_bytes().into());
+		for wall in self {
+			data.append(&mut wall.serialize());
+		}
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, len) = take_bytes(input, size_of::<u32>(), "list length")?;
+		let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+		// A corrupted length mustn't allocate gigabytes, every entry takes at least one byte
+		let mut vector = Vec::with_capacity(len.min(input.len()));
+		let mut input = input;
+		for _ in 0..len {
+			let (new_input, entry) = T::deserialize(input, version)?;
+			input = new_input;
+			vector.push(entry);
+		}
+		Ok((input, vector))
+	}
+}
+
+impl<T: Serialize> Serialize for Option<T> {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = vec![];
+		data.append(&mut self.is_some().serialize());
+		if let Some(value) = self {
+			data.append(&mut value.serialize());
+		}
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, is_some) = bool::deserialize(input, version)?;
+		if !is_some {
+			return Ok((input, None));
+		}
+		let (input, value) = T::deserialize(input, version)?;
+		Ok((input, Some(value)))
+	}
+}
+
+ImplSerializeByteForEnum!(WallSenseType);
+ImplSerializeByteForEnum!(DoorType);
+ImplSerializeByteForEnum!(DoorState);
+ImplSerializeByteForEnum!(WallDirection);
+ImplSerializeByteForEnum!(PolygonType);
+
+impl SerializeByte for bool {
+	fn serialize_byte(&self) -> u8 {
+		if *self {
+			1
+		} else {
+			0
+		}
+	}
+
+	fn deserialize_byte(input: &[u8]) -> ParseResult<'_, Self> {
+		let (rest, data) = take_bytes(input, 1, "boolean")?;
+		let data = match data[0] {
+			0 => false,
+			1 => true,
+			_ => return Err(ParseFailure::invalid_value(input, "boolean")),
+		};
+		Ok((rest, data))
+	}
+}
+
+pub struct TestCase {
+	pub call: RaycastingCall,
+	pub los: Vec<Point>,
+	pub fov: Vec<Point>,
+}
+
+impl TestCase {
+	/// Checks if the polygons match the expected ones. Points may be off by a small amount,
+	/// so rounding differences between platforms don't make tests fail.
+	pub fn matches(&self, los: &[Point], fov: &[Point]) -> bool {
+		is_same_polygon(&self.los, los) && is_same_polygon(&self.fov, fov)
+	}
+}
+
+pub(crate) fn is_same_polygon(expected: &[Point], actual: &[Point]) -> bool {
+	let e = 0.1;
+	expected.len() == actual.len()
+		&& expected
+			.iter()
+			.zip(actual)
+			.all(|(expected, actual)| expected.distance_to(actual) < e)
+}
+
+impl Serialize for TestCase {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = Vec::new();
+		data.append(&mut self.call.serialize());
+		data.append(&mut self.los.serialize());
+		data.append(&mut self.fov.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, call) = RaycastingCall::deserialize(input, version)?;
+		let (input, los) = Vec::deserialize(input, version)?;
+		let (input, fov) = Vec::deserialize(input, version)?;
+		Ok((input, Self { call, los, fov }))
+	}
+}
+
+/// All walls, roofs and sources of a scene. Some bugs only show up in the combination of the polygons of many sources,
+/// like artifacts in the union of the vision of several tokens.
+pub struct SceneSnapshot {
+	pub walls: Vec<WallBase>,
+	pub wall_ids: Vec<Option<String>>,
+	pub roofs: Vec<bool>,
+	pub tile_ids: Vec<Option<String>>,
+	pub sources: Vec<PolygonSource>,
+}
+
+impl SceneSnapshot {
+	pub fn new(cache: &Cache, sources: Vec<PolygonSource>) -> Self {
+		let (wall_ids, tile_ids) = ids_from_cache(cache);
+		Self {
+			walls: cache.walls.clone(),
+			wall_ids,
+			roofs: cache.tiles.occluded.clone(),
+			tile_ids,
+			sources,
+		}
+	}
+
+	pub fn build_cache(&self) -> Cache {
+		build_cache(&self.walls, &self.wall_ids, &self.roofs, &self.tile_ids)
+	}
+
+	/// Computes the polygons of all sources in order, using a cache built by `build_cache`
+	pub fn compute(&self, cache: &Cache) -> Vec<PolygonResult> {
+		compute_polygons(cache, &self.sources)
+	}
+
+	/// Extracts the call of a single source, so it can be minimized or drawn
+	pub fn call(&self, source: usize) -> RaycastingCall {
+		let source = &self.sources[source];
+		RaycastingCall {
+			walls: self.walls.clone(),
+			wall_ids: self.wall_ids.clone(),
+			roofs: self.roofs.clone(),
+			tile_ids: self.tile_ids.clone(),
+			origin: source.origin,
+			height: source.height,
+			radius: source.radius,
+			distance: source.distance,
+			density: source.density,
+			angle: source.angle,
+			rotation: source.rotation,
+			polygon_type: source.polygon_type,
+		}
+	}
+}
+
+impl Serialize for PolygonSource {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = Vec::new();
+		data.append(&mut self.origin.serialize());
+		data.append(&mut self.height.serialize());
+		data.append(&mut self.radius.serialize());
+		data.append(&mut self.distance.serialize());
+		data.append(&mut self.density.serialize());
+		data.append(&mut self.angle.serialize());
+		data.append(&mut self.rotation.serialize());
+		data.append(&mut self.polygon_type.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, origin) = Point::deserialize(input, version)?;
+		let (input, height) = f64::deserialize(input, version)?;
+		let (input, radius) = f64::deserialize(input, version)?;
+		let (input, distance) = f64::deserialize(input, version)?;
+		let (input, density) = f64::deserialize(input, version)?;
+		let (input, angle) = f64::deserialize(input, version)?;
+		let (input, rotation) = f64::deserialize(input, version)?;
+		let (input, polygon_type) = PolygonType::deserialize(input, version)?;
+		Ok((
+			input,
+			Self {
+				origin,
+				height,
+				radius,
+				distance,
+				density,
+				angle,
+				rotation,
+				polygon_type,
+			},
+		))
+	}
+}
+
+// Snapshots have been added in version 4, so older versions don't need to be handled
+impl Serialize for SceneSnapshot {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = serialize_walls(&self.walls, &self.wall_ids, &self.roofs, &self.tile_ids);
+		data.append(&mut self.sources.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, (walls, wall_ids, roofs, tile_ids)) = deserialize_walls(input, version)?;
+		let (input, sources) = Vec::deserialize(input, version)?;
+		Ok((
+			input,
+			Self {
+				walls,
+				wall_ids,
+				roofs,
+				tile_ids,
+				sources,
+			},
+		))
+	}
+}
+
+/// The expected polygons of every source of a scene, in the order of the sources
+pub struct SceneTestCase {
+	pub scene: SceneSnapshot,
+	pub polygons: Vec<(Vec<Point>, Vec<Point>)>,
+}
+
+impl SceneTestCase {
+	/// Computes the polygons of all sources and stores them as the expected ones.
+	/// Fails if any of the sources can't be computed.
+	pub fn generate(scene: SceneSnapshot) -> Result<Self, RaycastingError> {
+		let polygons = scene
+			.compute(&scene.build_cache())
+			.into_iter()
+			.collect::<Result<_, _>>()?;
+		Ok(Self { scene, polygons })
+	}
+
+	/// Returns the index of the first source whose polygons don't match the expected ones
+	pub fn first_mismatch(&self, results: &[PolygonResult]) -> Option<usize> {
+		if results.len() != self.polygons.len() {
+			return Some(results.len().min(self.polygons.len()));
+		}
+		self.polygons
+			.iter()
+			.zip(results)
+			.position(|((los, fov), result)| match result {
+				Ok((actual_los, actual_fov)) => {
+					!is_same_polygon(los, actual_los) || !is_same_polygon(fov, actual_fov)
+				}
+				Err(_) => true,
+			})
+	}
+
+	/// The test case of a single source, for use with the tools that work on calls
+	pub fn test_case(&self, source: usize) -> TestCase {
+		let (los, fov) = self.polygons[source].clone();
+		TestCase {
+			call: self.scene.call(source),
+			los,
+			fov,
+		}
+	}
+}
+
+impl Serialize for SceneTestCase {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = self.scene.serialize();
+		data.append(&mut self.polygons.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, scene) = SceneSnapshot::deserialize(input, version)?;
+		let (input, polygons) = Vec::deserialize(input, version)?;
+		Ok((input, Self { scene, polygons }))
+	}
+}
+
+pub fn serialize_ascii85<T: Serialize>(data: T) -> String {
+	encode(&data.serialize())
+}
+
+pub fn deserialize_ascii85<T: Serialize>(input: &str) -> Result<T, DeserializationError> {
+	let (version, input) = decode(input)?;
+	parse(&input, |input| T::deserialize(input, version))
+}
+
+fn encode(data: &[u8]) -> String {
+	let mut compressed = compress(data, Format::Zlib, CompressionLevel::BestSize).unwrap();
+	compressed.insert(0, CURRENT_VERSION);
+	ascii85::encode(&compressed)
+}
+
+// Returns the version and the decompressed data
+fn decode(input: &str) -> Result<(u8, Vec<u8>), DeserializationError> {
+	let input = ascii85::decode(input).map_err(|message| DeserializationError::InvalidAscii85 {
+		message: message.to_string(),
+	})?;
+	let (&version, input) = input.split_first().ok_or(DeserializationError::Empty)?;
+	if version > CURRENT_VERSION {
+		return Err(DeserializationError::UnsupportedVersion { version });
+	}
+	let (input, _) = decompress(input, Format::Zlib).map_err(|error| {
+		DeserializationError::InvalidCompression {
+			message: format!("{:?}", error),
+		}
+	})?;
+	Ok((version, input))
+}
+
+// Runs the parser on the complete data, so errors can be reported with their offset
+fn parse<'a, T, F: FnOnce(&'a [u8]) -> ParseResult<'a, T>>(
+	input: &'a [u8],
+	parser: F,
+) -> Result<T, DeserializationError> {
+	match parser(input) {
+		Ok((_, data)) => Ok(data),
+		Err(nom::Err::Error(failure)) | Err(nom::Err::Failure(failure)) => {
+			Err(failure.into_error(input.len()))
+		}
+		Err(nom::Err::Incomplete(_)) => unreachable!("The parsers only work on complete input"),
+	}
+}
+
+/// Reads data in either format. JSON is recognized by its leading brace, everything else is treated as ascii85.
+pub fn deserialize_any<T: Serialize + Json>(input: &str) -> Result<T, DeserializationError> {
+	let input = input.trim();
+	if input.starts_with('{') {
+		Ok(deserialize_json(input)?)
+	} else {
+		deserialize_ascii85(input)
+	}
+}
+
+pub fn ascii85_to_json<T: Serialize + Json>(input: &str) -> Result<String, DeserializationError> {
+	Ok(serialize_json(&deserialize_ascii85::<T>(input)?))
+}
+
+pub fn json_to_ascii85<T: Serialize + Json>(input: &str) -> Result<String, JsonError> {
+	Ok(serialize_ascii85(deserialize_json::<T>(input)?))
+}
+
+pub struct RaycastingCall {
+	pub walls: Vec<WallBase>,
+	/// The Foundry ids of the walls, in the same order as `walls`. Data from before version 4 doesn't contain them.
+	pub wall_ids: Vec<Option<String>>,
+	pub roofs: Vec<bool>,
+	/// The Foundry ids of the roof tiles, in the same order as `roofs`
+	pub tile_ids: Vec<Option<String>>,
+	pub origin: Point,
+	pub height: f64,
+	pub radius: f64,
+	pub distance: f64,
+	pub density: f64,
+	pub angle: f64,
+	pub rotation: f64,
+	pub polygon_type: PolygonType,
+}
+
+impl RaycastingCall {
+	pub fn new(cache: &Cache, source: &PolygonSource) -> Self {
+		let (wall_ids, tile_ids) = ids_from_cache(cache);
+		Self {
+			walls: cache.walls.clone(),
+			wall_ids,
+			roofs: cache.tiles.occluded.clone(),
+			tile_ids,
+			origin: source.origin,
+			height: source.height,
+			radius: source.radius,
+			distance: source.distance,
+			density: source.density,
+			angle: source.angle,
+			rotation: source.rotation,
+			polygon_type: source.polygon_type,
+		}
+	}
+
+	pub fn build_cache(&self) -> Cache {
+		build_cache(&self.walls, &self.wall_ids, &self.roofs, &self.tile_ids)
+	}
+
+	pub fn source(&self) -> PolygonSource {
+		PolygonSource {
+			origin: self.origin,
+			height: self.height,
+			radius: self.radius,
+			distance: self.distance,
+			density: self.density,
+			angle: self.angle,
+			rotation: self.rotation,
+			polygon_type: self.polygon_type,
+		}
+	}
+
+	/// Computes the polygons of the call using a cache built by `build_cache`
+	pub fn compute(&self, cache: &Cache) -> PolygonResult {
+		self.compute_with_internals(cache, None)
+	}
+
+	pub fn compute_with_internals(
+		&self,
+		cache: &Cache,
+		internals: Option<&mut Internals>,
+	) -> PolygonResult {
+		compute_polygon(
+			cache,
+			self.origin,
+			self.height,
+			self.radius,
+			self.distance,
+			self.density,
+			VisionAngle::from_rotation_and_angle(self.rotation, self.angle, self.origin),
+			self.polygon_type,
+			internals,
+		)
+	}
+}
+
+fn ids_from_cache(cache: &Cache) -> (Vec<Option<String>>, Vec<Option<String>>) {
+	(
+		ids_by_index(&cache.wall_ids, cache.walls.len()),
+		ids_by_index(&cache.tiles.id_map, cache.tiles.occluded.len()),
+	)
+}
+
+fn ids_by_index(ids: &FxHashMap<String, usize>, count: usize) -> Vec<Option<String>> {
+	let mut ids_by_index = vec![None; count];
+	for (id, &index) in ids {
+		ids_by_index[index] = Some(id.clone());
+	}
+	ids_by_index
+}
+
+fn build_cache(
+	walls: &[WallBase],
+	wall_ids: &[Option<String>],
+	roofs: &[bool],
+	tile_ids: &[Option<String>],
+) -> Cache {
+	let mut tiles = TileCache::from_roofs(roofs.to_vec());
+	tiles.id_map = ids_to_indices(tile_ids, roofs.len());
+	let mut cache = Cache::build(walls.to_vec(), tiles);
+	cache.wall_ids = ids_to_indices(wall_ids, walls.len());
+	cache
+}
+
+fn ids_to_indices(ids: &[Option<String>], count: usize) -> FxHashMap<String, usize> {
+	ids.iter()
+		.take(count)
+		.enumerate()
+		.filter_map(|(index, id)| id.clone().map(|id| (id, index)))
+		.collect()
+}
+
+/// Finds the first wall that belongs to a roof which doesn't exist. Such a wall would make building the cache panic.
+pub(crate) fn invalid_roof(walls: &[WallBase], roofs: &[bool]) -> Option<(usize, usize)> {
+	walls.iter().enumerate().find_map(|(i, wall)| {
+		wall.roof
+			.filter(|&roof| roof >= roofs.len())
+			.map(|roof| (i, roof))
+	})
+}
+
+// The ids are stored in fields of their own, which may have been changed without changing the entries as well.
+// Missing ids are treated as unknown and ids without an entry are ignored, so entries are never dropped.
+pub(crate) fn with_ids<T: Clone>(
+	entries: &[T],
+	ids: &[Option<String>],
+) -> Vec<(T, Option<String>)> {
+	entries
+		.iter()
+		.enumerate()
+		.map(|(index, entry)| (entry.clone(), ids.get(index).cloned().flatten()))
+		.collect()
+}
+
+// The ids are stored next to the entries they belong to, so their number always matches when reading them
+fn serialize_walls(
+	walls: &[WallBase],
+	wall_ids: &[Option<String>],
+	roofs: &[bool],
+	tile_ids: &[Option<String>],
+) -> Vec<u8> {
+	let mut data = with_ids(walls, wall_ids).serialize();
+	data.append(&mut with_ids(roofs, tile_ids).serialize());
+	data
+}
+
+type WallsAndRoofs = (
+	Vec<WallBase>,
+	Vec<Option<String>>,
+	Vec<bool>,
+	Vec<Option<String>>,
+);
+
+fn deserialize_walls(input: &[u8], version: u8) -> ParseResult<'_, WallsAndRoofs> {
+	let (input, (walls, wall_ids)) = if version >= 4 {
+		let (input, walls) = Vec::<(WallBase, Option<String>)>::deserialize(input, version)?;
+		(input, walls.into_iter().unzip())
+	} else {
+		let (input, walls) = Vec::<WallBase>::deserialize(input, version)?;
+		let wall_ids = vec![None; walls.len()];
+		(input, (walls, wall_ids))
+	};
+	let (input, (roofs, tile_ids)) = if version >= 4 {
+		let (input, roofs) = Vec::<(bool, Option<String>)>::deserialize(input, version)?;
+		(input, roofs.into_iter().unzip())
+	} else if version >= 3 {
+		let (input, roofs) = Vec::<bool>::deserialize(input, version)?;
+		let tile_ids = vec![None; roofs.len()];
+		(input, (roofs, tile_ids))
+	} else {
+		(input, (vec![], vec![]))
+	};
+	if let Some((wall, roof)) = invalid_roof(&walls, &roofs) {
+		return Err(ParseFailure::invalid_roof(wall, roof));
+	}
+	Ok((input, (walls, wall_ids, roofs, tile_ids)))
+}
+
+impl Serialize for RaycastingCall {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = serialize_walls(&self.walls, &self.wall_ids, &self.roofs, &self.tile_ids);
+		data.append(&mut self.origin.serialize());
+		data.append(&mut self.height.serialize());
+		data.append(&mut self.radius.serialize());
+		data.append(&mut self.distance.serialize());
+		data.append(&mut self.density.serialize());
+		data.append(&mut self.angle.serialize());
+		data.append(&mut self.rotation.serialize());
+		data.append(&mut self.polygon_type.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, (walls, wall_ids, roofs, tile_ids)) = deserialize_walls(input, version)?;
+		let (input, origin) = Point::deserialize(input, version)?;
+		let (input, height) = if version >= 2 {
+			f64::deserialize(input, version)?
+		} else {
+			(input, 0.0)
+		};
+		let (input, radius) = f64::deserialize(input, version)?;
+		let (input, distance) = f64::deserialize(input, version)?;
+		let (input, density) = f64::deserialize(input, version)?;
+		let (input, angle) = f64::deserialize(input, version)?;
+		let (input, rotation) = f64::deserialize(input, version)?;
+		let (input, polygon_type) = if version >= 3 {
+			PolygonType::deserialize(input, version)?
+		} else {
+			(input, PolygonType::SIGHT)
+		};
+		Ok((
+			input,
+			Self {
+				walls,
+				wall_ids,
+				roofs,
+				tile_ids,
+				origin,
+				height,
+				radius,
+				distance,
+				density,
+				angle,
+				rotation,
+				polygon_type,
+			},
+		))
+	}
+}
+
+impl Serialize for WallBase {
+	fn serialize(&self) -> Vec<u8> {
+		let mut data = Vec::with_capacity(size_of::<Self>());
+		data.append(&mut self.p1.serialize());
+		data.append(&mut self.p2.serialize());
+		data.append(&mut self.movement.serialize());
+		data.append(&mut self.sense.serialize());
+		data.append(&mut self.sound.serialize());
+		data.append(&mut self.door.serialize());
+		data.append(&mut self.ds.serialize());
+		data.append(&mut self.dir.serialize());
+		data.append(&mut self.height.serialize());
+		data.append(&mut self.roof.serialize());
+		data
+	}
+
+	fn deserialize(input: &[u8], version: u8) -> ParseResult<'_, Self> {
+		let (input, p1) = Point::deserialize(input, version)?;
+		let (input, p2) = Point::deserialize(input, version)?;
+		let (input, movement) = if version >= 3 {
+			WallSenseType::deserialize(input, version)?
+		} else {
+			(input, WallSenseType::NORMAL)
+		};
+		let (input, sense) = WallSenseType::deserialize(input, version)?;
+		let (input, sound) = if version >= 3 {
+			WallSenseType::deserialize(input, version)?
+		} else {
+			(input, sense)
+		};
+		let (input, door) = DoorType::deserialize(input, version)?;
+		let (input, ds) = DoorState::deserialize(input, version)?;
+		let (input, dir) = WallDirection::deserialize(input, version)?;
+		let (input, height) = if version >= 1 {
+			WallHeight::deserialize(input, version)?
+		} else {
+			(input, WallHeight::default())
+		};
+		let (input, roof) = if version >= 3 {
+			Option::deserialize(input, version)?
+		} else {
+			(input, None)
+		};
+		Ok((
+			input,
+			Self {
+				p1,
+				p2,
+				movement,
+				sense,
+				sound,
+				door,
+				ds,
+				dir,
+				height,
+				roof,
+			},
+		))
+	}
+}
@@ -0,0 +1,295 @@
+use crate::geometry::Point;
+use crate::raycasting::{
+	compute_polygon, Cache, DoorState, DoorType, Internals, VisionAngle, WallBase, WallDirection,
+	WallSenseType,
+};
+use crate::serialization::RaycastingCall;
+use std::fmt::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+// The colors match the ones used by `_visualizeSight` in the Foundry module
+const LOS_COLOR: &str = "#66ffff";
+const FOV_COLOR: &str = "#ff66ff";
+const EXPECTED_COLOR: &str = "#0000ff";
+const ENDPOINT_COLOR: &str = "#00ffff";
+const INTERSECTION_COLOR: &str = "#ff0000";
+const RADIUS_COLOR: &str = "#ff0000";
+const VISION_ANGLE_COLOR: &str = "#ff9900";
+const ORIGIN_COLOR: &str = "#00aa00";
+
+/// Draws the scene of the call together with the polygons it results in.
+/// If `expected` contains the expected LOS and FOV, they are outlined as well, so differences are easy to spot.
+pub fn render_svg(call: &RaycastingCall, expected: Option<(&[Point], &[Point])>) -> String {
+	let cache = call.build_cache();
+	let mut internals = Internals::default();
+	// Broken calls are the ones that need to be looked at the most, so panics are drawn like errors
+	let result = catch_unwind(AssertUnwindSafe(|| {
+		compute_polygon(
+			&cache,
+			call.origin,
+			call.height,
+			call.radius,
+			call.distance,
+			call.density,
+			VisionAngle::from_rotation_and_angle(call.rotation, call.angle, call.origin),
+			call.polygon_type,
+			Some(&mut internals),
+		)
+		.map_err(|error| error.to_string())
+	}))
+	.unwrap_or_else(|_| Err("Panicked".to_owned()));
+
+	let mut points = vec![call.origin];
+	points.extend(call.walls.iter().flat_map(|wall| [wall.p1, wall.p2]));
+	if let Ok((los, fov)) = &result {
+		points.extend(los.iter().chain(fov));
+	}
+	if let Some((los, fov)) = expected {
+		points.extend(los.iter().chain(fov));
+	}
+	let view = View::enclosing(&points);
+
+	let mut svg = String::new();
+	writeln!(
+		svg,
+		r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+		view.min.x, view.min.y, view.width, view.height
+	)
+	.unwrap();
+	writeln!(
+		svg,
+		r#"<rect x="{}" y="{}" width="{}" height="{}" fill="white"/>"#,
+		view.min.x, view.min.y, view.width, view.height
+	)
+	.unwrap();
+
+	match &result {
+		Ok((los, fov)) => {
+			polygon(&mut svg, los, LOS_COLOR, 0.2, "none", &view);
+			polygon(&mut svg, fov, FOV_COLOR, 0.2, "none", &view);
+		}
+		Err(error) => {
+			writeln!(
+				svg,
+				r#"<text x="{}" y="{}" font-size="{}" fill="red">{}</text>"#,
+				view.min.x + view.margin,
+				view.min.y + view.margin,
+				view.stroke * 12.0,
+				escape(error)
+			)
+			.unwrap();
+		}
+	}
+	if let Some((los, fov)) = expected {
+		polygon(&mut svg, los, "none", 0.0, EXPECTED_COLOR, &view);
+		polygon(&mut svg, fov, "none", 0.0, EXPECTED_COLOR, &view);
+	}
+
+	if call.radius.is_finite() {
+		writeln!(
+			svg,
+			r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="{}"/>"#,
+			call.origin.x, call.origin.y, call.radius, RADIUS_COLOR, view.stroke
+		)
+		.unwrap();
+	}
+	if let Some(vision_angle) =
+		VisionAngle::from_rotation_and_angle(call.rotation, call.angle, call.origin)
+	{
+		let length = view.width.max(view.height);
+		for angle in [vision_angle.start, vision_angle.end] {
+			let end = Point::new(
+				call.origin.x - angle.cos() * length,
+				call.origin.y - angle.sin() * length,
+			);
+			line(
+				&mut svg,
+				call.origin,
+				end,
+				VISION_ANGLE_COLOR,
+				view.stroke,
+				"",
+			);
+		}
+	}
+
+	for wall in &call.walls {
+		draw_wall(&mut svg, wall, &cache, call, &view);
+	}
+
+	for intersection in &cache.intersections {
+		cross(&mut svg, intersection.point, INTERSECTION_COLOR, &view);
+	}
+	for endpoint in &internals.endpoints {
+		let color = if endpoint.is_intersection {
+			INTERSECTION_COLOR
+		} else {
+			ENDPOINT_COLOR
+		};
+		circle(&mut svg, Point::new(endpoint.x, endpoint.y), color, &view);
+	}
+	circle(&mut svg, call.origin, ORIGIN_COLOR, &view);
+
+	svg.push_str("</svg>\n");
+	svg
+}
+
+struct View {
+	min: Point,
+	width: f64,
+	height: f64,
+	margin: f64,
+	/// The width of lines, so they have roughly the same thickness regardless of the scene's size
+	stroke: f64,
+}
+
+impl View {
+	fn enclosing(points: &[Point]) -> Self {
+		let finite = points
+			.iter()
+			.filter(|point| point.x.is_finite() && point.y.is_finite());
+		let mut min = Point::new(f64::INFINITY, f64::INFINITY);
+		let mut max = Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+		for point in finite {
+			min = Point::new(min.x.min(point.x), min.y.min(point.y));
+			max = Point::new(max.x.max(point.x), max.y.max(point.y));
+		}
+		let size = (max.x - min.x).max(max.y - min.y).max(1.0);
+		let margin = size * 0.05;
+		Self {
+			min: Point::new(min.x - margin, min.y - margin),
+			width: max.x - min.x + 2.0 * margin,
+			height: max.y - min.y + 2.0 * margin,
+			margin,
+			stroke: size / 500.0,
+		}
+	}
+}
+
+// Walls are colored by their sense for the call's polygon type. Walls that currently don't block anything, like open
+// doors or walls below the source, are dashed. Doors get a square in their middle and directional walls a tick that
+// points to the side from which they block.
+fn draw_wall(svg: &mut String, wall: &WallBase, cache: &Cache, call: &RaycastingCall, view: &View) {
+	let color = match wall.current_sense(cache, call.polygon_type) {
+		WallSenseType::NORMAL => "#000000",
+		WallSenseType::LIMITED => "#228822",
+		WallSenseType::NONE => "#aaaaaa",
+	};
+	let active = wall.effective_sense(cache, call.height, call.polygon_type) != WallSenseType::NONE;
+	let dash = if active {
+		String::new()
+	} else {
+		format!(
+			r#" stroke-dasharray="{} {}""#,
+			view.stroke * 4.0,
+			view.stroke * 4.0
+		)
+	};
+	line(svg, wall.p1, wall.p2, color, view.stroke * 2.0, &dash);
+
+	let middle = Point::new((wall.p1.x + wall.p2.x) / 2.0, (wall.p1.y + wall.p2.y) / 2.0);
+	let length = wall.p1.distance_to(&wall.p2);
+	if wall.dir != WallDirection::BOTH && length > 0.0 {
+		// The normal points to the side for which `orient2d(p1, p2, side)` is negative.
+		// That's the side from which LEFT walls block, while RIGHT walls block from the other side.
+		let normal = Point::new(
+			(wall.p2.y - wall.p1.y) / length,
+			(wall.p1.x - wall.p2.x) / length,
+		);
+		let side = if wall.dir == WallDirection::LEFT {
+			1.0
+		} else {
+			-1.0
+		};
+		let tick = view.stroke * 8.0 * side;
+		let end = Point::new(middle.x + normal.x * tick, middle.y + normal.y * tick);
+		line(svg, middle, end, color, view.stroke * 2.0, "");
+	}
+
+	if wall.door != DoorType::NONE {
+		let door_color = match wall.door {
+			DoorType::SECRET => "#aa00aa",
+			_ => "#7777ff",
+		};
+		let fill = match wall.ds {
+			DoorState::OPEN => "white",
+			DoorState::CLOSED => door_color,
+			DoorState::LOCKED => "#ff0000",
+		};
+		let size = view.stroke * 8.0;
+		writeln!(
+			svg,
+			r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}"/>"#,
+			middle.x - size / 2.0,
+			middle.y - size / 2.0,
+			size,
+			size,
+			fill,
+			door_color,
+			view.stroke
+		)
+		.unwrap();
+	}
+}
+
+fn polygon(
+	svg: &mut String,
+	points: &[Point],
+	fill: &str,
+	fill_opacity: f64,
+	stroke: &str,
+	view: &View,
+) {
+	let points = points
+		.iter()
+		.map(|point| format!("{},{}", point.x, point.y))
+		.collect::<Vec<_>>()
+		.join(" ");
+	writeln!(
+		svg,
+		r#"<polygon points="{}" fill="{}" fill-opacity="{}" stroke="{}" stroke-width="{}"/>"#,
+		points, fill, fill_opacity, stroke, view.stroke
+	)
+	.unwrap();
+}
+
+fn line(svg: &mut String, p1: Point, p2: Point, color: &str, width: f64, attributes: &str) {
+	writeln!(
+		svg,
+		r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"{}/>"#,
+		p1.x, p1.y, p2.x, p2.y, color, width, attributes
+	)
+	.unwrap();
+}
+
+fn circle(svg: &mut String, center: Point, color: &str, view: &View) {
+	writeln!(
+		svg,
+		r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+		center.x,
+		center.y,
+		view.stroke * 3.0,
+		color
+	)
+	.unwrap();
+}
+
+fn cross(svg: &mut String, center: Point, color: &str, view: &View) {
+	let size = view.stroke * 4.0;
+	for (dx, dy) in [(size, size), (size, -size)] {
+		line(
+			svg,
+			Point::new(center.x - dx, center.y - dy),
+			Point::new(center.x + dx, center.y + dy),
+			color,
+			view.stroke,
+			"",
+		);
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
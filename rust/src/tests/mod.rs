@@ -1,36 +1,97 @@
-use std::fs::read_to_string;
+mod random_scene;
+mod reference;
+
+use std::f64::consts::PI;
+use std::fs::{read_dir, read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use random_scene::{random_scene, Rng, SCENE_SIZE};
+use yazi::{compress, CompressionLevel, Format};
 
 use crate::{
-	raycasting::{compute_polygon, Cache, TileCache, VisionAngle},
-	serialization::{deserialize_ascii85, TestCase},
+	benchmark::{measure, synthetic_scenes},
+	geometry::{orient2d, polygon_contains, segments_cross, Point},
+	minimize::{minimize, Failure},
+	raycasting::{
+		compute_polygon, compute_polygon_extended, compute_polygon_with_fallback, compute_polygons,
+		find_collision, is_point_visible, sample_polygon, validate_polygons, Cache, Collision,
+		DoorState, DoorType, EdgeSource, Internals, PolygonDefect, PolygonKind, PolygonResult,
+		PolygonSource, PolygonType, RaycastingError, TileCache, VisionAngle, WallBase,
+		WallDirection, WallGrid, WallHeight, WallSenseType,
+	},
+	serialization::{
+		ascii85_to_json, deserialize_any, deserialize_ascii85, deserialize_cache, deserialize_json,
+		import_foundry_scene, json_to_ascii85, serialize_ascii85, serialize_cache, serialize_json,
+		DeserializationError, RaycastingCall, SceneSnapshot, SceneTestCase, Serialize, TestCase,
+	},
+	svg::render_svg,
 };
 
+// Test cases can be stored as ascii85 or as JSON, which is easier to review and edit
+fn load_test(filename: &str) -> TestCase {
+	let json_path = format!("tests/{}.json", filename);
+	if Path::new(&json_path).exists() {
+		return deserialize_json(&read_to_string(json_path).unwrap()).unwrap();
+	}
+	deserialize_ascii85(&read_to_string(format!("tests/{}.ascii85", filename)).unwrap()).unwrap()
+}
+
+fn all_tests() -> Vec<(PathBuf, TestCase)> {
+	let mut paths = read_dir("tests/")
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| {
+			path.extension()
+				.is_some_and(|extension| extension == "ascii85" || extension == "json")
+		})
+		.collect::<Vec<_>>();
+	paths.sort();
+	paths
+		.into_iter()
+		.map(|path| {
+			let test = deserialize_any(&read_to_string(&path).unwrap()).unwrap();
+			(path, test)
+		})
+		.collect()
+}
+
 fn run_test(filename: &str) {
-	let test_root_dir = "tests/".to_owned();
-	let test = deserialize_ascii85::<TestCase>(
-		&read_to_string(test_root_dir + filename + ".ascii85").unwrap(),
+	let test = load_test(filename);
+	let cache = Cache::build(
+		test.call.walls.clone(),
+		TileCache::from_roofs(test.call.roofs.clone()),
 	);
-	let cache = Cache::build(test.call.walls, TileCache::from_roofs(test.call.roofs));
-	let (los, fov) = compute_polygon(
-		&cache,
-		test.call.origin,
-		test.call.height,
-		test.call.radius,
-		test.call.distance,
-		test.call.density,
-		VisionAngle::from_rotation_and_angle(test.call.rotation, test.call.angle, test.call.origin),
-		test.call.polygon_type,
-		None,
-	);
-	let e = 0.1;
-	assert_eq!(test.los.len(), los.len());
-	for (expected, actual) in test.los.iter().zip(los) {
-		assert!(expected.distance_to(&actual) < e);
+	let mut internals = Internals {
+		validate: true,
+		..Internals::default()
+	};
+	let result = test
+		.call
+		.compute_with_internals(&cache, Some(&mut internals));
+	// Draw failing test cases next to their file, so the difference can be inspected
+	if !matches!(&result, Ok((los, fov)) if test.matches(los, fov)) {
+		let svg = render_svg(&test.call, Some((&test.los, &test.fov)));
+		write(format!("tests/{}.svg", filename), svg).unwrap();
 	}
+	assert_test_result(&test, result);
+}
 
-	assert_eq!(test.fov.len(), fov.len());
-	for (expected, actual) in test.fov.iter().zip(fov) {
-		assert!(expected.distance_to(&actual) < e);
+fn check_test_result(cache: &Cache, test: &TestCase) {
+	assert_test_result(test, test.call.compute(cache));
+}
+
+fn assert_test_result(test: &TestCase, result: PolygonResult) {
+	let (los, fov) = result.unwrap();
+	assert_same_polygon(&test.los, &los);
+	assert_same_polygon(&test.fov, &fov);
+}
+
+fn assert_same_polygon(expected: &[Point], actual: &[Point]) {
+	let e = 0.1;
+	assert_eq!(expected.len(), actual.len());
+	for (expected, actual) in expected.iter().zip(actual) {
+		assert!(expected.distance_to(actual) < e);
 	}
 }
 
@@ -43,6 +104,1153 @@ macro_rules! raytracing_test (
 	};
 );
 
+#[test]
+fn incremental_cache_updates() {
+	let test = load_test("29-minimally_intersecting_walls");
+	let mut cache = Cache::build(Vec::new(), TileCache::from_roofs(test.call.roofs.clone()));
+	for (i, wall) in test.call.walls.iter().enumerate() {
+		cache.insert_wall(i.to_string(), *wall);
+	}
+	check_test_result(&cache, &test);
+
+	// Removing and re-adding walls moves them around in the cache, which must not change the result
+	for i in (0..test.call.walls.len()).step_by(2) {
+		assert!(cache.remove_wall(&i.to_string()).is_some());
+	}
+	for i in (0..test.call.walls.len()).step_by(2) {
+		cache.insert_wall(i.to_string(), test.call.walls[i]);
+	}
+	check_test_result(&cache, &test);
+
+	let full_build = Cache::build(test.call.walls.clone(), TileCache::default());
+	assert!(!cache.intersections.is_empty());
+	assert_eq!(full_build.intersections.len(), cache.intersections.len());
+	for intersection in &cache.intersections {
+		assert!(full_build
+			.intersections
+			.iter()
+			.any(|other| other.point.is_same_as(&intersection.point)));
+	}
+}
+
+#[test]
+fn door_state_updates_match_rebuild() {
+	let mut call = load_test("29-minimally_intersecting_walls").call;
+	for wall in call.walls.iter_mut().step_by(3) {
+		wall.door = DoorType::DOOR;
+	}
+	call.wall_ids = (0..call.walls.len()).map(|i| Some(i.to_string())).collect();
+	let mut cache = call.build_cache();
+	let closed = call.compute(&cache).unwrap();
+	for ds in [
+		DoorState::OPEN,
+		DoorState::LOCKED,
+		DoorState::CLOSED,
+		DoorState::OPEN,
+	] {
+		for i in (0..call.walls.len()).step_by(3) {
+			assert!(cache.set_door_state(&i.to_string(), ds));
+			call.walls[i].ds = ds;
+		}
+		let polygons = call.compute(&cache).unwrap();
+		assert_eq!(polygons, call.compute(&call.build_cache()).unwrap());
+		// Only open doors can be seen through
+		assert_eq!(polygons == closed, ds != DoorState::OPEN);
+	}
+	assert!(!cache.set_door_state("unknown", DoorState::OPEN));
+}
+
+fn wall(p1: Point, p2: Point) -> WallBase {
+	WallBase::new(
+		p1,
+		p2,
+		WallSenseType::NORMAL,
+		WallSenseType::NORMAL,
+		WallSenseType::NORMAL,
+		DoorType::NONE,
+		DoorState::CLOSED,
+		WallDirection::BOTH,
+		WallHeight::default(),
+		None,
+	)
+}
+
+fn assert_same_intersections(walls: &[WallBase]) {
+	let expected = Cache::calc_intersections_brute_force(walls);
+	let actual = Cache::build(walls.to_vec(), TileCache::default()).intersections;
+	assert_eq!(expected.len(), actual.len());
+	for (expected, actual) in expected.iter().zip(actual.iter()) {
+		assert_eq!(expected.walls, actual.walls);
+		assert_eq!(expected.point, actual.point);
+	}
+}
+
+#[test]
+fn grid_intersections_match_brute_force() {
+	for (_, test) in all_tests() {
+		assert_same_intersections(&test.call.walls);
+	}
+
+	// A dense lattice of diagonal walls, some of them long enough to span many grid cells
+	let mut walls = Vec::new();
+	for i in 0..40 {
+		let offset = i as f64 * 37.0;
+		let length = if i % 7 == 0 { 1500.0 } else { 90.0 };
+		for (p1, p2) in [
+			(Point::new(offset, 0.0), Point::new(offset + length, length)),
+			(
+				Point::new(offset, 100.0),
+				Point::new(offset + length, 100.0 - length),
+			),
+			(
+				Point::new(offset + 0.5, -20.0),
+				Point::new(offset + 0.5, 200.0),
+			),
+		] {
+			walls.push(wall(p1, p2));
+		}
+	}
+	assert!(Cache::calc_intersections_brute_force(&walls).len() > 400);
+	assert_same_intersections(&walls);
+}
+
+#[test]
+fn huge_walls_in_grid() {
+	// Walls that span too many cells to be traversed are checked against all other walls
+	let mut walls = (0..20)
+		.map(|i| {
+			let x = i as f64 * 50.0;
+			wall(Point::new(x, 0.0), Point::new(x + 20.0, 100.0))
+		})
+		.collect::<Vec<_>>();
+	walls.push(wall(Point::new(-1e12, 50.0), Point::new(1e12, 50.0)));
+	assert_same_intersections(&walls);
+
+	// Coordinates that aren't finite can't be located in the grid at all, but mustn't stall the precomputation
+	walls.push(wall(
+		Point::new(f64::NEG_INFINITY, 70.0),
+		Point::new(f64::INFINITY, 70.0),
+	));
+	Cache::build(walls, TileCache::default());
+}
+
+#[test]
+fn walls_outside_of_radius_are_culled() {
+	// A big map full of short walls with some long walls crossing it
+	let mut walls = Vec::new();
+	for x in 0..60 {
+		for y in 0..60 {
+			let p = Point::new(x as f64 * 100.0 + 13.0, y as f64 * 100.0 + 41.0);
+			walls.push(wall(p, Point::new(p.x + 30.0, p.y + (x % 5) as f64 * 10.0)));
+		}
+	}
+	walls.push(wall(Point::new(0.0, 2950.0), Point::new(6000.0, 3080.0)));
+	walls.push(wall(Point::new(2980.0, 0.0), Point::new(3070.0, 6000.0)));
+	// A room that encloses the LOS of sources inside of it within their radius
+	let room = [
+		Point::new(850.0, 870.0),
+		Point::new(1150.0, 870.0),
+		Point::new(1150.0, 1170.0),
+		Point::new(850.0, 1170.0),
+	];
+	for i in 0..room.len() {
+		walls.push(wall(room[i], room[(i + 1) % room.len()]));
+	}
+
+	let cache = Cache::build(walls.clone(), TileCache::default());
+	// Without any occupied cells the grid never culls, which gives the result of considering all walls
+	let mut unculled_cache = Cache::build(walls, TileCache::default());
+	unculled_cache.grid = WallGrid::new(100.0);
+	let radius = 310.0;
+	// The LOS reaches much further than the radius, like it does for the sources in Foundry
+	let distance = 10000.0;
+	// Walls beyond the radius still shape the LOS of the first origin
+	for (origin, enclosed) in [
+		(Point::new(3005.0, 3020.0), false),
+		(Point::new(1005.0, 1020.0), true),
+	] {
+		for angle in [360.0, 90.0, 270.0] {
+			let compute = |cache: &Cache, internals: &mut Internals| {
+				compute_polygon(
+					cache,
+					origin,
+					0.0,
+					radius,
+					distance,
+					6.0,
+					VisionAngle::from_rotation_and_angle(45.0, angle, origin),
+					PolygonType::SIGHT,
+					Some(internals),
+				)
+				.unwrap()
+			};
+			let mut unculled = Internals::default();
+			let mut culled = Internals::default();
+			let (culled_los, culled_fov) = compute(&cache, &mut culled);
+			let (los, fov) = compute(&unculled_cache, &mut unculled);
+			// The endpoints of hidden walls on the sides of a vision cone may change the rounding of the points there
+			if angle == 360.0 {
+				assert_eq!((&culled_los, &culled_fov), (&los, &fov));
+			} else {
+				assert_same_polygon(&los, &culled_los);
+				assert_same_polygon(&fov, &culled_fov);
+			}
+			if enclosed {
+				assert!(culled.endpoints.len() * 10 < unculled.endpoints.len());
+			}
+		}
+	}
+}
+
+#[test]
+fn point_visibility_matches_los() {
+	for (_, test) in all_tests() {
+		let TestCase { call, los, .. } = test;
+		if VisionAngle::from_rotation_and_angle(call.rotation, call.angle, call.origin).is_some() {
+			continue;
+		}
+		let cache = Cache::build(call.walls, TileCache::from_roofs(call.roofs));
+		let range = call.distance.min(1000.0);
+		let steps = 40;
+		for x in -steps..=steps {
+			for y in -steps..=steps {
+				let target = Point::new(
+					call.origin.x + range * x as f64 / steps as f64,
+					call.origin.y + range * y as f64 / steps as f64,
+				);
+				let close_to_edge = los.iter().enumerate().any(|(i, p1)| {
+					let p2 = los[(i + 1) % los.len()];
+					target.distance_to_segment(p1, &p2) < 1.0
+				});
+				if close_to_edge || call.origin.distance_to(&target) > call.distance * 0.99 {
+					continue;
+				}
+				assert_eq!(
+					polygon_contains(&los, target),
+					is_point_visible(&cache, call.origin, call.height, target, call.polygon_type),
+					"{:?}",
+					target
+				);
+			}
+		}
+	}
+}
+
+#[test]
+fn movement_collisions() {
+	let mut open_door = wall(Point::new(100.0, -50.0), Point::new(100.0, 50.0));
+	open_door.door = DoorType::DOOR;
+	open_door.ds = DoorState::OPEN;
+	let mut no_movement = wall(Point::new(200.0, -50.0), Point::new(200.0, 50.0));
+	no_movement.movement = WallSenseType::NONE;
+	let mut directional = wall(Point::new(300.0, -50.0), Point::new(300.0, 50.0));
+	directional.dir = WallDirection::LEFT;
+	let mut low = wall(Point::new(400.0, -50.0), Point::new(400.0, 50.0));
+	low.height = WallHeight {
+		top: 10.0,
+		bottom: f64::NEG_INFINITY,
+	};
+	let blocking = wall(Point::new(500.0, -50.0), Point::new(500.0, 50.0));
+	let cache = Cache::build(
+		vec![open_door, no_movement, directional, low, blocking],
+		TileCache::default(),
+	);
+
+	let collision = |from: Point, to: Point, height: f64| find_collision(&cache, from, to, height);
+	// The directional wall only blocks in one direction
+	assert_eq!(
+		collision(Point::new(0.0, 0.0), Point::new(600.0, 0.0), 20.0),
+		Some(Collision {
+			point: Point::new(500.0, 0.0),
+			wall: 4
+		})
+	);
+	assert_eq!(
+		collision(Point::new(350.0, 10.0), Point::new(250.0, 10.0), 20.0),
+		Some(Collision {
+			point: Point::new(300.0, 10.0),
+			wall: 2
+		})
+	);
+	// Low walls only block tokens that aren't above them
+	assert_eq!(
+		collision(Point::new(350.0, 0.0), Point::new(600.0, 0.0), 0.0),
+		Some(Collision {
+			point: Point::new(400.0, 0.0),
+			wall: 3
+		})
+	);
+	// Closing the door makes it block movement
+	let mut cache = cache;
+	cache.wall_ids.insert("door".to_owned(), 0);
+	cache.set_door_state("door", DoorState::CLOSED);
+	assert_eq!(
+		find_collision(&cache, Point::new(0.0, 0.0), Point::new(150.0, 0.0), 0.0),
+		Some(Collision {
+			point: Point::new(100.0, 0.0),
+			wall: 0
+		})
+	);
+	// Paths that end right before a wall or start on a wall are free
+	assert_eq!(
+		find_collision(&cache, Point::new(110.0, 0.0), Point::new(499.0, 0.0), 20.0),
+		None
+	);
+	assert_eq!(
+		find_collision(&cache, Point::new(500.0, 0.0), Point::new(600.0, 0.0), 20.0),
+		None
+	);
+	// Removing a wall moves the last wall to its index, so the id is needed to keep track of a wall
+	cache.wall_ids.insert("blocking".to_owned(), 4);
+	cache.remove_wall("door");
+	let collision = find_collision(&cache, Point::new(0.0, 0.0), Point::new(600.0, 0.0), 20.0);
+	assert_eq!(collision.unwrap().wall, cache.wall_ids["blocking"]);
+	assert_eq!(cache.wall_ids["blocking"], 0);
+}
+
+#[test]
+fn batch_computation_matches_single_computations() {
+	for (_, TestCase { call, .. }) in all_tests() {
+		let cache = Cache::build(call.walls, TileCache::from_roofs(call.roofs));
+		let mut sources = Vec::new();
+		for &(x, y) in &[(0.0, 0.0), (150.0, 0.0), (-75.0, 230.0), (10.0, -500.0)] {
+			for &polygon_type in &[call.polygon_type, PolygonType::LIGHT, PolygonType::SOUND] {
+				sources.push(PolygonSource {
+					origin: Point::new(call.origin.x + x, call.origin.y + y),
+					height: call.height,
+					radius: call.radius,
+					distance: call.distance,
+					density: call.density,
+					angle: call.angle,
+					rotation: call.rotation,
+					polygon_type,
+				});
+			}
+		}
+		let batch = compute_polygons(&cache, &sources);
+		assert_eq!(batch.len(), sources.len());
+		for (source, result) in sources.iter().zip(batch) {
+			let (los, fov) = result.unwrap();
+			let (expected_los, expected_fov) = compute_polygon(
+				&cache,
+				source.origin,
+				source.height,
+				source.radius,
+				source.distance,
+				source.density,
+				VisionAngle::from_rotation_and_angle(source.rotation, source.angle, source.origin),
+				source.polygon_type,
+				None,
+			)
+			.unwrap();
+			assert_same_polygon(&expected_los, &los);
+			assert_same_polygon(&expected_fov, &fov);
+		}
+	}
+}
+
+fn all_scene_tests() -> Vec<SceneTestCase> {
+	let mut paths = read_dir("tests/scenes/")
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| {
+			path.extension()
+				.is_some_and(|extension| extension == "ascii85")
+		})
+		.collect::<Vec<_>>();
+	paths.sort();
+	paths
+		.into_iter()
+		.map(|path| deserialize_any(&read_to_string(&path).unwrap()).unwrap())
+		.collect()
+}
+
+#[test]
+fn scene_tests() {
+	let scene_tests = all_scene_tests();
+	assert!(!scene_tests.is_empty());
+	for test in scene_tests {
+		assert!(test.scene.sources.len() > 1);
+		let cache = test.scene.build_cache();
+		let results = test.scene.compute(&cache);
+		assert_eq!(test.first_mismatch(&results), None);
+
+		// Replaying the whole scene gives the same result as computing every source on its own
+		for (i, result) in results.iter().enumerate() {
+			let single = test.test_case(i);
+			assert_eq!(single.call.walls.len(), test.scene.walls.len());
+			let (los, fov) = result.as_ref().unwrap();
+			assert!(single.matches(los, fov));
+			assert_eq!(
+				single.call.compute(&single.call.build_cache()).unwrap(),
+				(los.clone(), fov.clone())
+			);
+		}
+
+		let json = serialize_json(&test);
+		let parsed = deserialize_json::<SceneTestCase>(&json).unwrap();
+		assert_eq!(parsed.serialize(), test.serialize());
+		let ascii85 = serialize_ascii85(parsed);
+		let parsed = deserialize_ascii85::<SceneTestCase>(&ascii85).unwrap();
+		assert_eq!(parsed.serialize(), test.serialize());
+	}
+
+	// A changed source is detected as such
+	let mut test = all_scene_tests().remove(0);
+	test.scene.sources[1].origin.x += 10.0;
+	let results = test.scene.compute(&test.scene.build_cache());
+	assert_eq!(test.first_mismatch(&results), Some(1));
+}
+
+#[test]
+fn cache_snapshots() {
+	let restore = |stored: &str, cache: Cache| {
+		deserialize_cache(stored, cache.walls, cache.wall_ids, cache.tiles).unwrap()
+	};
+	// Only walls with an id can be matched to the stored ones
+	let name_walls = |scene: &mut SceneSnapshot| {
+		scene.wall_ids = (0..scene.walls.len())
+			.map(|i| Some(i.to_string()))
+			.collect();
+	};
+	for mut test in all_scene_tests() {
+		name_walls(&mut test.scene);
+		let scene = &test.scene;
+		let cache = scene.build_cache();
+		let stored = serialize_cache(&cache);
+		let restored = restore(&stored, scene.build_cache()).unwrap();
+		assert_eq!(restored.serialize(), cache.serialize());
+		assert_eq!(restored.intersections.len(), cache.intersections.len());
+		let (mut expected, mut candidates) = (vec![], vec![]);
+		for source in &scene.sources {
+			let end = Point::new(source.origin.x + source.radius, source.origin.y);
+			cache
+				.grid
+				.segment_candidates(source.origin, end, &mut expected);
+			restored
+				.grid
+				.segment_candidates(source.origin, end, &mut candidates);
+			assert_eq!(candidates, expected);
+		}
+		assert_eq!(test.first_mismatch(&scene.compute(&restored)), None);
+
+		// The snapshot can be used for the same walls in a different order
+		let reversed = SceneSnapshot {
+			walls: scene.walls.iter().rev().cloned().collect(),
+			wall_ids: scene.wall_ids.iter().rev().cloned().collect(),
+			roofs: scene.roofs.clone(),
+			tile_ids: scene.tile_ids.clone(),
+			sources: vec![],
+		};
+		let restored = restore(&stored, reversed.build_cache()).unwrap();
+		assert_eq!(restored.serialize(), cache.serialize());
+
+		// Opening a door doesn't invalidate the snapshot, but the restored cache has the current door state
+		let mut opened = scene.build_cache();
+		opened.walls[0].door = DoorType::DOOR;
+		opened.walls[0].ds = DoorState::OPEN;
+		let restored = restore(&stored, opened).unwrap();
+		assert_eq!(restored.walls[0].door, DoorType::DOOR);
+		assert_eq!(restored.walls[0].ds, DoorState::OPEN);
+
+		// The snapshot can't be used once a wall has moved
+		let mut moved = scene.build_cache();
+		moved.walls[0].p1.x += 1.0;
+		assert!(restore(&stored, moved).is_none());
+		let mut unnamed = scene.build_cache();
+		unnamed.wall_ids.clear();
+		assert!(restore(&stored, unnamed).is_none());
+	}
+
+	let mut scene = all_scene_tests().remove(0).scene;
+	name_walls(&mut scene);
+	let mut cache = scene.build_cache();
+	let index = cache.walls.len();
+	cache.grid.oversized.push(index);
+	assert_eq!(
+		deserialize_cache(
+			&serialize_cache(&cache),
+			cache.walls,
+			cache.wall_ids,
+			cache.tiles
+		)
+		.err(),
+		Some(DeserializationError::InvalidWallIndex { index })
+	);
+}
+
+#[test]
+fn synthetic_scenes_are_valid() {
+	for (name, scene) in synthetic_scenes() {
+		let measurement = measure(&scene, 1).unwrap_or_else(|error| panic!("{}: {}", name, error));
+		assert!(measurement.phases.prepare_data > Duration::ZERO);
+		assert!(measurement.phases.calculate_los > Duration::ZERO);
+		assert!(measurement.total() > measurement.build);
+
+		let results = scene.compute(&scene.build_cache());
+		for (source, result) in scene.sources.iter().zip(results) {
+			let (los, fov) = result.unwrap();
+			let defects = validate_polygons(
+				source.origin,
+				source.radius,
+				source.distance,
+				source.density,
+				&los,
+				&fov,
+			);
+			assert_eq!(defects, vec![], "{}", name);
+		}
+	}
+
+	// Timings are only measured on request
+	let call = synthetic_scenes().remove(1).1.call(0);
+	let mut internals = Internals::default();
+	call.compute_with_internals(&call.build_cache(), Some(&mut internals))
+		.unwrap();
+	assert!(!internals.endpoints.is_empty());
+	assert!(internals.timings.is_none());
+}
+
+#[test]
+fn edge_sources_match_geometry() {
+	let e = 0.1;
+	for (path, TestCase { call, .. }) in all_tests() {
+		let cache = Cache::build(
+			call.walls.clone(),
+			TileCache::from_roofs(call.roofs.clone()),
+		);
+		let (los, fov) = compute_polygon_extended(
+			&cache,
+			call.origin,
+			call.height,
+			call.radius,
+			call.distance,
+			call.density,
+			VisionAngle::from_rotation_and_angle(call.rotation, call.angle, call.origin),
+			call.polygon_type,
+			None,
+		)
+		.unwrap();
+		for (polygon, radius) in &[(los, call.distance), (fov, call.radius)] {
+			assert_eq!(polygon.points.len(), polygon.edges.len());
+			for (i, edge) in polygon.edges.iter().enumerate() {
+				let p1 = polygon.points[i];
+				let p2 = polygon.points[(i + 1) % polygon.points.len()];
+				let on_ray = |p: Point| {
+					let cross = (p1.x - call.origin.x) * (p.y - call.origin.y)
+						- (p1.y - call.origin.y) * (p.x - call.origin.x);
+					cross.abs() / p1.distance_to(&call.origin).max(1.0) < e
+				};
+				let valid = match *edge {
+					EdgeSource::Wall(index) => {
+						let wall = &cache.walls[index];
+						p1.distance_to_segment(&wall.p1, &wall.p2) < e
+							&& p2.distance_to_segment(&wall.p1, &wall.p2) < e
+					}
+					EdgeSource::Arc => {
+						(p1.distance_to(&call.origin) - radius).abs() < e
+							&& (p2.distance_to(&call.origin) - radius).abs() < e
+					}
+					EdgeSource::Wedge => p1.is_same_as(&call.origin) || p2.is_same_as(&call.origin),
+					EdgeSource::Shadow => on_ray(p2),
+				};
+				assert!(valid, "{:?}: {:?} from {:?} to {:?}", path, edge, p1, p2);
+			}
+		}
+	}
+}
+
+#[test]
+fn invalid_input_returns_errors() {
+	let test = load_test("29-minimally_intersecting_walls");
+	let cache = test.call.build_cache();
+	let source = test.call.source();
+	let invalid_source = PolygonSource {
+		origin: Point::new(f64::NAN, test.call.origin.y),
+		..source
+	};
+	let results = compute_polygons(&cache, &[invalid_source, source]);
+	assert!(matches!(
+		results[0],
+		Err(RaycastingError::InvalidOrigin { .. })
+	));
+	let (los, fov) = results[1].as_ref().unwrap();
+	assert_same_polygon(&test.los, los);
+	assert_same_polygon(&test.fov, fov);
+
+	// Walls are only culled if the radius is finite, so an infinite radius makes sure the broken wall is considered
+	let mut broken_call = load_test("29-minimally_intersecting_walls").call;
+	broken_call
+		.walls
+		.push(wall(Point::new(f64::NAN, 0.0), Point::new(0.0, 0.0)));
+	let broken_cache = broken_call.build_cache();
+	let unculled_source = PolygonSource {
+		radius: f64::INFINITY,
+		..source
+	};
+	let results = compute_polygons(&broken_cache, &[unculled_source, unculled_source]);
+	for result in results {
+		assert!(matches!(result, Err(RaycastingError::InvalidAngle { .. })));
+	}
+	// The failed computations must not leave anything behind that influences the next one
+	check_test_result(&cache, &test);
+}
+
+#[test]
+fn validation_detects_defects() {
+	let origin = Point::new(0.0, 0.0);
+	let square = |size: f64, offset: f64| {
+		vec![
+			Point::new(offset - size, offset - size),
+			Point::new(offset + size, offset - size),
+			Point::new(offset + size, offset + size),
+			Point::new(offset - size, offset + size),
+		]
+	};
+	let validate =
+		|los: &[Point], fov: &[Point]| validate_polygons(origin, 20.0, 20.0, 6.0, los, fov);
+	let los = square(10.0, 0.0);
+	let fov = square(5.0, 0.0);
+	assert_eq!(validate(&los, &fov), vec![]);
+
+	let mut reversed = los.clone();
+	reversed.reverse();
+	assert!(
+		validate(&reversed, &fov).contains(&PolygonDefect::InconsistentWinding {
+			polygon: PolygonKind::Los,
+			edge: 0
+		})
+	);
+
+	let bowtie = vec![los[0], los[1], los[3], los[2]];
+	assert!(
+		validate(&bowtie, &fov).contains(&PolygonDefect::SelfIntersection {
+			polygon: PolygonKind::Los,
+			edges: (1, 3)
+		})
+	);
+
+	// Parts of a polygon that doesn't contain the origin necessarily run around it clockwise
+	let offset = square(5.0, 10.0);
+	assert!(
+		validate(&offset, &offset).contains(&PolygonDefect::OriginOutside {
+			polygon: PolygonKind::Los
+		})
+	);
+
+	// The LOS may be bigger than the radius, the FOV may not
+	let big = square(30.0, 0.0);
+	assert_eq!(validate(&big, &fov), vec![]);
+	assert_eq!(
+		validate(&big, &big),
+		(0..4)
+			.map(|index| PolygonDefect::FovOutsideRadius { index })
+			.collect::<Vec<_>>()
+	);
+	assert_eq!(
+		validate(&fov, &los),
+		(0..4)
+			.map(|index| PolygonDefect::FovOutsideLos { index })
+			.collect::<Vec<_>>()
+	);
+
+	// A cone whose apex is at the origin is valid, even though the origin appears twice
+	let cone = vec![
+		origin,
+		origin,
+		Point::new(10.0, -10.0),
+		Point::new(10.0, 10.0),
+	];
+	assert_eq!(validate(&cone, &cone), vec![]);
+
+	// Release builds only validate computed polygons on request, without any walls an infinite distance gives an invalid LOS
+	let mut call = load_test("29-minimally_intersecting_walls").call;
+	call.walls.clear();
+	call.distance = f64::INFINITY;
+	let cache = call.build_cache();
+	assert_eq!(call.compute(&cache).is_ok(), !cfg!(debug_assertions));
+	let mut internals = Internals {
+		validate: true,
+		..Internals::default()
+	};
+	assert!(matches!(
+		call.compute_with_internals(&cache, Some(&mut internals)),
+		Err(RaycastingError::InvalidPolygon { .. })
+	));
+}
+
+#[test]
+fn minimizer_keeps_failing_walls() {
+	let test = load_test("29-minimally_intersecting_walls");
+	let wall_count = test.call.walls.len();
+
+	// An invalid wall makes every computation fail, so at most that wall needs to be kept.
+	// Without any walls the infinite distance leads to an invalid polygon, which is a failure as well.
+	let mut call = load_test("29-minimally_intersecting_walls").call;
+	let broken_wall = wall(Point::new(f64::NAN, 0.0), Point::new(0.0, 0.0));
+	call.walls.insert(wall_count / 2, broken_wall);
+	call.radius = f64::INFINITY;
+	call.distance = f64::INFINITY;
+	let reproducer = minimize(call, &Failure::Error).unwrap();
+	assert!(reproducer.call.walls.len() <= 1);
+	assert!(reproducer.call.walls.iter().all(|wall| wall.p1.x.is_nan()));
+	assert!(Failure::Error.occurs(&reproducer.call));
+	assert!(reproducer.los.is_empty() && reproducer.fov.is_empty());
+
+	let correct = Failure::Mismatch {
+		los: test.los.clone(),
+		fov: test.fov.clone(),
+	};
+	assert!(minimize(load_test("29-minimally_intersecting_walls").call, &correct).is_none());
+
+	// Walls that shape the LOS can't be removed without changing the correct result, but hidden walls can
+	let mut wrong_los = test.los.clone();
+	wrong_los[0].x += 1.0;
+	let mismatch = Failure::Mismatch {
+		los: wrong_los,
+		fov: test.fov.clone(),
+	};
+	let reproducer = minimize(test.call, &mismatch).unwrap();
+	assert!(reproducer.call.walls.len() < wall_count);
+	let cache = Cache::build(
+		reproducer.call.walls.clone(),
+		TileCache::from_roofs(reproducer.call.roofs.clone()),
+	);
+	let correct_test = TestCase {
+		los: test.los,
+		fov: test.fov,
+		..reproducer
+	};
+	check_test_result(&cache, &correct_test);
+}
+
+#[test]
+fn svg_shows_scene_and_polygons() {
+	let test = load_test("4-directional_walls");
+	let svg = render_svg(&test.call, Some((&test.los, &test.fov)));
+	assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+	// The computed and the expected polygons
+	assert_eq!(svg.matches("<polygon").count(), 4);
+	assert!(svg.matches("<line").count() >= test.call.walls.len());
+
+	let mut call = test.call;
+	call.origin = Point::new(f64::NAN, 0.0);
+	let svg = render_svg(&call, None);
+	assert_eq!(svg.matches("<polygon").count(), 0);
+	assert!(svg.contains("<text"));
+}
+
+#[test]
+fn predicates_are_exact() {
+	// Rounding makes a naive evaluation of the determinant report these points as collinear
+	let a = Point::new(0.5, 0.5);
+	let b = Point::new(12.0, 12.0);
+	let c = Point::new(24.0, 24.0);
+	let c_left = Point::new(24.0, 24.0 + 24.0 * f64::EPSILON);
+	assert_eq!(orient2d(a, b, c), 0.0);
+	assert!(orient2d(a, b, c_left) > 0.0);
+	assert!(orient2d(b, a, c_left) < 0.0);
+
+	let p1 = Point::new(0.0, 0.0);
+	let p2 = Point::new(10.0, 0.0);
+	// T-junctions only touch
+	assert!(!segments_cross(
+		p1,
+		p2,
+		Point::new(5.0, 0.0),
+		Point::new(5.0, 5.0)
+	));
+	assert!(segments_cross(
+		p1,
+		p2,
+		Point::new(5.0, -1e-300),
+		Point::new(5.0, 5.0)
+	));
+	// Collinear segments don't cross
+	assert!(!segments_cross(
+		p1,
+		p2,
+		Point::new(5.0, 0.0),
+		Point::new(15.0, 0.0)
+	));
+}
+
+#[test]
+fn almost_vertical_and_almost_parallel_walls() {
+	// A room whose walls are tilted by a tiny amount, so none of them is axis aligned
+	let corners = [
+		Point::new(0.0, 0.0),
+		Point::new(1000.0, 1e-9),
+		Point::new(1000.0 - 1e-9, 1000.0),
+		Point::new(1e-9, 1000.0 + 1e-9),
+	];
+	let mut walls = (0..corners.len())
+		.map(|i| wall(corners[i], corners[(i + 1) % corners.len()]))
+		.collect::<Vec<_>>();
+	// A wall just in front of the right wall that is almost parallel to it
+	let inner_x = 1000.0 - 1e-6;
+	walls.push(wall(
+		Point::new(inner_x, 100.0),
+		Point::new(inner_x - 1e-12, 900.0),
+	));
+	let cache = Cache::build(walls, TileCache::default());
+	let origin = Point::new(500.0, 500.0);
+	let (los, _) = compute_polygon(
+		&cache,
+		origin,
+		0.0,
+		2000.0,
+		2000.0,
+		5.0,
+		None,
+		PolygonType::SIGHT,
+		None,
+	)
+	.unwrap();
+	for point in &los {
+		assert!(
+			point.x >= -1e-6 && point.x <= 1000.0 + 1e-6,
+			"{:?} is outside of the room",
+			point
+		);
+		assert!(
+			point.y >= -1e-6 && point.y <= 1000.0 + 1e-6,
+			"{:?} is outside of the room",
+			point
+		);
+		if point.y > 100.0 + 1e-6 && point.y < 900.0 - 1e-6 {
+			assert!(
+				point.x <= inner_x + 1e-9,
+				"{:?} is behind the inner wall",
+				point
+			);
+		}
+	}
+}
+
+#[test]
+fn fallback_samples_polygon_on_failure() {
+	let test = load_test("29-minimally_intersecting_walls");
+	let cache = test.call.build_cache();
+	let source = test.call.source();
+	let polygon = compute_polygon_with_fallback(&cache, &source).unwrap();
+	assert!(!polygon.used_fallback());
+	assert!(polygon.call.is_none());
+	assert_same_polygon(&test.los, &polygon.los);
+	assert_same_polygon(&test.fov, &polygon.fov);
+
+	let mut broken_call = load_test("29-minimally_intersecting_walls").call;
+	broken_call
+		.walls
+		.push(wall(Point::new(f64::NAN, 0.0), Point::new(0.0, 0.0)));
+	let broken_cache = broken_call.build_cache();
+	let unculled_source = PolygonSource {
+		radius: f64::INFINITY,
+		..source
+	};
+	let polygon = compute_polygon_with_fallback(&broken_cache, &unculled_source).unwrap();
+	assert_eq!(
+		sample_polygon(&broken_cache, &unculled_source).unwrap(),
+		(polygon.los.clone(), polygon.fov.clone())
+	);
+	assert!(matches!(
+		polygon.error,
+		Some(RaycastingError::InvalidAngle { .. })
+	));
+	assert!(polygon.los.len() >= (360.0 / source.density) as usize);
+	assert_eq!(polygon.los.len(), polygon.fov.len());
+	for point in &polygon.fov {
+		assert!(point.distance_to(&source.origin) <= source.radius + 0.001);
+	}
+	let call = deserialize_ascii85::<RaycastingCall>(&polygon.call.unwrap()).unwrap();
+	assert_eq!(call.walls.len(), test.call.walls.len() + 1);
+	assert_eq!(call.origin, source.origin);
+	assert_eq!(call.radius, f64::INFINITY);
+
+	// Rays that don't hit any wall end at the radius if the distance is infinite
+	let empty_cache = Cache::build(Vec::new(), TileCache::default());
+	let infinite_source = PolygonSource {
+		distance: f64::INFINITY,
+		..source
+	};
+	let (los, fov) = sample_polygon(&empty_cache, &infinite_source).unwrap();
+	for point in los.iter().chain(&fov) {
+		assert!((point.distance_to(&source.origin) - source.radius).abs() < 0.001);
+	}
+
+	// Neither the regular computation nor the sampling would ever finish without a positive density
+	for density in [0.0, -6.0, f64::NAN, f64::INFINITY] {
+		let source = PolygonSource { density, ..source };
+		assert!(matches!(
+			compute_polygon_with_fallback(&broken_cache, &source),
+			Err(RaycastingError::InvalidDensity { .. })
+		));
+		assert!(matches!(
+			sample_polygon(&cache, &source),
+			Err(RaycastingError::InvalidDensity { .. })
+		));
+	}
+}
+
+// The random scenes contain directional walls, limited walls, doors, wall heights and roofs and use all polygon types,
+// with and without vision angles
+#[test]
+fn los_matches_reference_raycaster() {
+	for seed in 0..2000 {
+		let mut rng = Rng::new(seed);
+		let call = random_scene(&mut rng);
+		let cache = call.build_cache();
+		// Failing scenes can be turned into a regular test case with `generateTest`
+		let serialized = || serialize_ascii85(RaycastingCall::new(&cache, &call.source()));
+		let mut internals = Internals {
+			validate: true,
+			..Internals::default()
+		};
+		let (los, _) = call
+			.compute_with_internals(&cache, Some(&mut internals))
+			.unwrap_or_else(|error| panic!("Seed {}: {}\n{}", seed, error, serialized()));
+
+		let critical_angles = reference::critical_angles(&call, &cache);
+		for _ in 0..360 {
+			let angle = rng.range(-PI, PI);
+			if critical_angles.iter().any(|&critical| {
+				let difference = (angle - critical).abs();
+				difference.min(2.0 * PI - difference) < 1e-6
+			}) {
+				continue;
+			}
+			let expected = reference::los_distance(&call, angle);
+			let actual = reference::polygon_distance(&los, call.origin, angle, call.distance);
+			assert!(
+				(expected - actual).abs() < SCENE_SIZE * 1e-6,
+				"Seed {}, angle {}: expected a distance of {}, but the polygon has {}\n{}",
+				seed,
+				angle,
+				expected,
+				actual,
+				serialized()
+			);
+		}
+	}
+}
+
+#[test]
+fn json_round_trips_exactly() {
+	for (_, test) in all_tests() {
+		let json = serialize_json(&test);
+		let parsed = deserialize_json::<TestCase>(&json).unwrap();
+		assert_eq!(test.serialize(), parsed.serialize());
+		let ascii85 = serialize_ascii85(test);
+		assert_eq!(
+			json_to_ascii85::<TestCase>(&ascii85_to_json::<TestCase>(&ascii85).unwrap()).unwrap(),
+			ascii85
+		);
+	}
+
+	// Values that are easily lost on the way through JSON
+	let mut call = load_test("4-directional_walls").call;
+	call.roofs = vec![false, true];
+	call.tile_ids = vec![Some("UkF8s0kDq1bVYe5w".to_owned()), None];
+	call.wall_ids[1] = Some("a \"quoted\" id".to_owned());
+	call.walls[0].roof = Some(1);
+	call.walls[0].height = WallHeight {
+		top: f64::INFINITY,
+		bottom: -0.0,
+	};
+	call.walls[1].height.bottom = f64::NEG_INFINITY;
+	call.walls[1].p1 = Point::new(0.1 + 0.2, 1e-300);
+	call.origin = Point::new(f64::MAX, f64::MIN_POSITIVE);
+	let parsed = deserialize_json::<RaycastingCall>(&serialize_json(&call)).unwrap();
+	assert_eq!(call.serialize(), parsed.serialize());
+
+	let error = deserialize_json::<RaycastingCall>(
+		r#"{"walls": [{"p1": {"x": 0, "y": 0}, "p2": {"x": 1, "y": "1"}}]}"#,
+	)
+	.err()
+	.unwrap();
+	assert_eq!(error.to_string(), "walls[0].p2.y: Expected a number");
+}
+
+// Writes raw bytes the way `serialize_ascii85` writes valid data
+fn encode_raw(version: u8, data: &[u8]) -> String {
+	let mut compressed = compress(data, Format::Zlib, CompressionLevel::BestSize).unwrap();
+	compressed.insert(0, version);
+	ascii85::encode(&compressed)
+}
+
+#[test]
+fn malformed_data_is_reported() {
+	let read = |input: &str| deserialize_ascii85::<RaycastingCall>(input).err().unwrap();
+	let call = load_test("4-directional_walls").call;
+	let data = call.serialize();
+
+	// The wall count takes 4 bytes and is followed by the first coordinate
+	assert_eq!(
+		read(&encode_raw(4, &data[..10])),
+		DeserializationError::UnexpectedEnd {
+			offset: 4,
+			expected: "number"
+		}
+	);
+	// The movement sense follows the two points of the first wall
+	let mut corrupted = data.clone();
+	corrupted[36] = 7;
+	let error = read(&encode_raw(4, &corrupted));
+	assert_eq!(
+		error,
+		DeserializationError::InvalidValue {
+			offset: 36,
+			value: 7,
+			expected: "WallSenseType"
+		}
+	);
+	assert_eq!(
+		error.to_string(),
+		"Byte 36 has the value 7, which isn't a valid WallSenseType"
+	);
+	assert_eq!(
+		read(&encode_raw(9, &data)),
+		DeserializationError::UnsupportedVersion { version: 9 }
+	);
+	assert_eq!(read(""), DeserializationError::Empty);
+	assert!(matches!(
+		read("not ascii85 at all ~~~"),
+		DeserializationError::InvalidAscii85 { .. }
+	));
+
+	// Inputs that have been cut off anywhere before the closing `~>` are incomplete
+	let ascii85 = serialize_ascii85(call);
+	for length in 0..ascii85.len() - 2 {
+		assert!(deserialize_ascii85::<RaycastingCall>(&ascii85[..length]).is_err());
+	}
+
+	let mut call = load_test("4-directional_walls").call;
+	call.walls[2].roof = Some(3);
+	let ascii85 = serialize_ascii85(call);
+	assert_eq!(
+		read(&ascii85),
+		DeserializationError::InvalidRoof { wall: 2, roof: 3 }
+	);
+	let error = deserialize_json::<RaycastingCall>(
+		&ascii85_to_json::<RaycastingCall>(&serialize_ascii85(
+			load_test("4-directional_walls").call,
+		))
+		.unwrap()
+		.replacen(r#""roof": null"#, r#""roof": 3"#, 1),
+	)
+	.err()
+	.unwrap();
+	assert_eq!(error.to_string(), "walls[0].roof: Roof 3 doesn't exist");
+}
+
+#[test]
+fn ids_survive_serialization() {
+	// The fixtures predate the ids
+	let test = load_test("4-directional_walls");
+	assert!(test.call.wall_ids.iter().all(Option::is_none));
+
+	let mut call = test.call;
+	call.wall_ids = (0..call.walls.len())
+		.map(|i| Some(format!("wall{}", i)))
+		.collect();
+	call.wall_ids[1] = None;
+	call.roofs = vec![true, false];
+	call.tile_ids = vec![Some("roof0".to_owned()), Some("roof1".to_owned())];
+	call.walls[0].roof = Some(1);
+	let parsed = deserialize_ascii85::<RaycastingCall>(&serialize_ascii85(call)).unwrap();
+	assert_eq!(parsed.wall_ids[0].as_deref(), Some("wall0"));
+	assert_eq!(parsed.wall_ids[1], None);
+	assert_eq!(parsed.tile_ids[1].as_deref(), Some("roof1"));
+
+	let cache = parsed.build_cache();
+	assert_eq!(cache.wall_ids["wall2"], 2);
+	assert_eq!(cache.tiles.id_map["roof1"], 1);
+	let call = RaycastingCall::new(&cache, &parsed.source());
+	assert_eq!(call.wall_ids, parsed.wall_ids);
+	assert_eq!(call.tile_ids, parsed.tile_ids);
+
+	// Walls are kept if the number of ids doesn't match
+	let mut call = parsed;
+	let wall_count = call.walls.len();
+	call.wall_ids.clear();
+	call.tile_ids.push(Some("removed roof".to_owned()));
+	assert_eq!(call.build_cache().tiles.id_map.len(), 2);
+	let parsed = deserialize_json::<RaycastingCall>(&serialize_json(&call)).unwrap();
+	assert_eq!(parsed.walls.len(), wall_count);
+	assert_eq!(parsed.tile_ids.len(), 2);
+	let parsed = deserialize_ascii85::<RaycastingCall>(&serialize_ascii85(call)).unwrap();
+	assert_eq!(parsed.walls.len(), wall_count);
+	assert_eq!(parsed.wall_ids, vec![None; wall_count]);
+	assert_eq!(parsed.tile_ids.len(), 2);
+}
+
+#[test]
+fn foundry_scene_import() {
+	// Walls in the format of Foundry v0.8 and v0.7, which doesn't have movement and sound restrictions
+	let export = r#"{
+		"name": "Bug report",
+		"walls": [
+			{"_id": "north", "c": [0, 0, 1000, 0], "move": 1, "sense": 1, "sound": 1, "door": 0, "ds": 0, "dir": 0, "flags": {}},
+			{"_id": "east", "c": [1000, 0, 1000, 1000], "move": 1, "sense": 1, "sound": 1, "door": 1, "ds": 1, "dir": 0, "flags": {}},
+			{"_id": "south", "c": [1000, 1000, 0, 1000.4], "move": 0, "sense": 2, "door": 0, "ds": 0},
+			{"_id": "west", "c": [0, 1000, 0, 0], "move": 1, "sense": 1, "sound": 1, "door": 0, "ds": 0, "dir": 1,
+				"flags": {"wallHeight": {"wallHeightTop": 10, "wallHeightBottom": null}}},
+			{"_id": "inner", "c": [200, 200, 300, 300], "move": 1, "sense": 1, "sound": 1, "door": 0, "ds": 0, "dir": 0}
+		],
+		"tiles": [
+			{"_id": "floor", "x": 0, "y": 0, "width": 1000, "height": 1000, "overhead": false, "occlusion": {"mode": 2}},
+			{"_id": "roof", "x": 100, "y": 100, "width": 300, "height": 300, "rotation": 90, "overhead": true,
+				"occlusion": {"mode": 2, "alpha": 0}}
+		]
+	}"#;
+	let cache = import_foundry_scene(export, true).unwrap();
+	assert_eq!(cache.walls.len(), 5);
+	let wall = |id: &str| cache.walls[cache.wall_ids[id]];
+	assert_eq!(wall("east").door, DoorType::DOOR);
+	assert_eq!(wall("east").ds, DoorState::OPEN);
+	assert_eq!(wall("south").p2, Point::new(0.0, 1000.0));
+	assert_eq!(wall("south").movement, WallSenseType::NONE);
+	assert_eq!(wall("south").sound, WallSenseType::LIMITED);
+	assert_eq!(wall("west").dir, WallDirection::LEFT);
+	assert_eq!(wall("west").height.top, 10.0);
+	assert_eq!(wall("west").height.bottom, f64::NEG_INFINITY);
+	assert_eq!(wall("north").roof, None);
+	assert_eq!(wall("inner").roof, Some(cache.tiles.id_map["roof"]));
+	assert_eq!(cache.tiles.occluded, vec![false]);
+	let (los, _) = compute_polygon(
+		&cache,
+		Point::new(700.0, 500.0),
+		0.0,
+		2000.0,
+		2000.0,
+		6.0,
+		None,
+		PolygonType::SIGHT,
+		None,
+	)
+	.unwrap();
+	// The open door doesn't block sight, so the LOS leaves the room
+	assert!(los.iter().any(|point| point.x > 1000.0));
+
+	let heightless = import_foundry_scene(export, false).unwrap();
+	assert_eq!(
+		heightless.walls[heightless.wall_ids["west"]].height.top,
+		f64::INFINITY
+	);
+
+	let error = import_foundry_scene(
+		r#"{"walls": [{"_id": "a", "c": [0, 0, 1, 1], "door": 5}]}"#,
+		true,
+	)
+	.err()
+	.unwrap();
+	assert_eq!(error.to_string(), "walls[0].door: 5 isn't a valid value");
+	let error = import_foundry_scene(r#"{"walls": [{"_id": "a", "c": [0, 0, 1]}]}"#, true)
+		.err()
+		.unwrap();
+	assert_eq!(error.to_string(), "walls[0].c: Expected 4 coordinates");
+}
+
 raytracing_test!(
 	limited_vision_angle_over_180_hidden_overflowing_wall,
 	"limited_vision_angle_over_180_hidden_overflowing_wall"
@@ -117,3 +1325,12 @@ raytracing_test!(
 	limited_angle_wall_to_the_right_with_start_visible_issue_32,
 	"32-limited_angle_wall_to_the_right_with_start_visible"
 );
+raytracing_test!(
+	wall_reentering_overflowing_vision_angle,
+	"wall_reentering_overflowing_vision_angle"
+);
+raytracing_test!(
+	identical_walls_crossed_by_wall,
+	"identical_walls_crossed_by_wall"
+);
+raytracing_test!(t_junction_at_ray, "t_junction_at_ray");
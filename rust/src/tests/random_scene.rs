@@ -0,0 +1,157 @@
+use crate::geometry::Point;
+use crate::raycasting::{
+	DoorState, DoorType, PolygonType, WallBase, WallDirection, WallHeight, WallSenseType,
+};
+use crate::serialization::RaycastingCall;
+
+pub use crate::benchmark::Rng;
+
+/// The size of the square every scene is enclosed in
+pub const SCENE_SIZE: f64 = 1000.0;
+
+/// Generates a scene with random walls inside of a closed room and a random source inside of that room
+pub fn random_scene(rng: &mut Rng) -> RaycastingCall {
+	let corners = [
+		Point::new(0.0, 0.0),
+		Point::new(SCENE_SIZE, 0.0),
+		Point::new(SCENE_SIZE, SCENE_SIZE),
+		Point::new(0.0, SCENE_SIZE),
+	];
+	let mut walls = (0..corners.len())
+		.map(|i| simple_wall(corners[i], corners[(i + 1) % corners.len()]))
+		.collect::<Vec<_>>();
+
+	let roof_count = rng.below(3);
+	let roofs: Vec<bool> = (0..roof_count).map(|_| rng.chance(0.5)).collect();
+
+	let wall_count = 3 + rng.below(20);
+	for _ in 0..wall_count {
+		// Walls in real scenes are often connected to each other
+		let p1 = if rng.chance(0.3) {
+			let other: &WallBase = &walls[rng.below(walls.len())];
+			if rng.chance(0.5) {
+				other.p1
+			} else {
+				other.p2
+			}
+		} else {
+			random_point(rng)
+		};
+		let p2 = if rng.chance(0.5) {
+			random_point(rng)
+		} else {
+			// Short walls lead to more walls that are close to each other
+			Point::new(
+				(p1.x + rng.range(-150.0, 150.0)).clamp(0.0, SCENE_SIZE),
+				(p1.y + rng.range(-150.0, 150.0)).clamp(0.0, SCENE_SIZE),
+			)
+		};
+		if p1 == p2 {
+			continue;
+		}
+		let height = if rng.chance(0.2) {
+			let bottom = rng.range(-10.0, 10.0);
+			WallHeight {
+				bottom,
+				top: bottom + rng.range(0.0, 20.0),
+			}
+		} else {
+			WallHeight::default()
+		};
+		let roof = if roof_count > 0 && rng.chance(0.3) {
+			Some(rng.below(roof_count))
+		} else {
+			None
+		};
+		let (door, ds) = if rng.chance(0.1) {
+			let ds = [DoorState::CLOSED, DoorState::OPEN, DoorState::LOCKED][rng.below(3)];
+			(DoorType::DOOR, ds)
+		} else {
+			(DoorType::NONE, DoorState::CLOSED)
+		};
+		walls.push(WallBase::new(
+			p1,
+			p2,
+			random_sense(rng),
+			random_sense(rng),
+			random_sense(rng),
+			door,
+			ds,
+			[
+				WallDirection::BOTH,
+				WallDirection::BOTH,
+				WallDirection::LEFT,
+				WallDirection::RIGHT,
+			][rng.below(4)],
+			height,
+			roof,
+		));
+	}
+
+	let (angle, rotation) = if rng.chance(0.5) {
+		(360.0, 0.0)
+	} else {
+		(rng.range(10.0, 350.0), rng.range(0.0, 360.0))
+	};
+	RaycastingCall {
+		wall_ids: vec![None; walls.len()],
+		walls,
+		tile_ids: vec![None; roofs.len()],
+		roofs,
+		// Unlike wall coordinates the origin isn't rounded, so it doesn't end up on a wall
+		origin: Point::new(
+			rng.range(1.0, SCENE_SIZE - 1.0),
+			rng.range(1.0, SCENE_SIZE - 1.0),
+		),
+		height: rng.range(0.0, 10.0),
+		// The whole room is within the range of the source, so the LOS polygon is bounded by walls only
+		radius: SCENE_SIZE * 2.0,
+		distance: SCENE_SIZE * 2.0,
+		density: 6.0,
+		angle,
+		rotation,
+		polygon_type: [
+			PolygonType::SIGHT,
+			PolygonType::LIGHT,
+			PolygonType::SOUND,
+			PolygonType::MOVEMENT,
+		][rng.below(4)],
+	}
+}
+
+fn random_point(rng: &mut Rng) -> Point {
+	// Whole numbers are common in real scenes and lead to many walls that share coordinates
+	let mut coordinate = || {
+		if rng.chance(0.5) {
+			(rng.range(1.0, SCENE_SIZE - 1.0)).round()
+		} else {
+			rng.range(1.0, SCENE_SIZE - 1.0)
+		}
+	};
+	Point::new(coordinate(), coordinate())
+}
+
+fn random_sense(rng: &mut Rng) -> WallSenseType {
+	[
+		WallSenseType::NORMAL,
+		WallSenseType::NORMAL,
+		WallSenseType::NORMAL,
+		WallSenseType::LIMITED,
+		WallSenseType::NONE,
+	][rng.below(5)]
+}
+
+fn simple_wall(p1: Point, p2: Point) -> WallBase {
+	WallBase::new(
+		p1,
+		p2,
+		WallSenseType::NORMAL,
+		WallSenseType::NORMAL,
+		WallSenseType::NORMAL,
+		DoorType::NONE,
+		DoorState::CLOSED,
+		WallDirection::BOTH,
+		WallHeight::default(),
+		None,
+	)
+}
@@ -0,0 +1,133 @@
+use crate::geometry::{orient2d, segment_intersection_parameters, Point};
+use crate::raycasting::{
+	Cache, DoorState, DoorType, PolygonType, VisionAngle, WallBase, WallDirection, WallSenseType,
+};
+use crate::serialization::RaycastingCall;
+
+// A deliberately simple raycaster that casts single rays against all walls of a scene.
+// It doesn't share any of the logic of the real implementation, so both can be checked against each other.
+
+/// The distance from the origin to the point where the ray with the given angle is blocked.
+/// Like everywhere else, the point at `angle` and distance `d` is located at `origin - (cos(angle), sin(angle)) * d`.
+/// Rays outside of the vision angle have a distance of 0.
+pub fn los_distance(call: &RaycastingCall, angle: f64) -> f64 {
+	if let Some(vision_angle) =
+		VisionAngle::from_rotation_and_angle(call.rotation, call.angle, call.origin)
+	{
+		let inside = if vision_angle.start < vision_angle.end {
+			angle >= vision_angle.start && angle <= vision_angle.end
+		} else {
+			angle >= vision_angle.start || angle <= vision_angle.end
+		};
+		if !inside {
+			return 0.0;
+		}
+	}
+
+	let origin = call.origin;
+	let target = Point::new(
+		origin.x - angle.cos() * call.distance,
+		origin.y - angle.sin() * call.distance,
+	);
+	let mut hits = call
+		.walls
+		.iter()
+		.filter_map(|wall| {
+			let sense = sense(call, wall);
+			if sense == WallSenseType::NONE || is_see_through(call, wall) {
+				return None;
+			}
+			let (t, u) = segment_intersection_parameters(origin, target, wall.p1, wall.p2)?;
+			if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+				Some((t, sense))
+			} else {
+				None
+			}
+		})
+		.collect::<Vec<_>>();
+	hits.sort_by(|(t1, _), (t2, _)| t1.partial_cmp(t2).unwrap());
+
+	// The ray passes through the first limited wall, but is stopped by every wall after that
+	let mut passed_limited_wall = false;
+	for (t, sense) in hits {
+		if sense == WallSenseType::LIMITED && !passed_limited_wall {
+			passed_limited_wall = true;
+			continue;
+		}
+		return t * call.distance;
+	}
+	call.distance
+}
+
+/// The angles at which the distance of the LOS may jump. Rays very close to these angles can't be compared reliably.
+pub fn critical_angles(call: &RaycastingCall, cache: &Cache) -> Vec<f64> {
+	let origin = call.origin;
+	let angle_of = |point: Point| (origin.y - point.y).atan2(origin.x - point.x);
+	let mut angles = call
+		.walls
+		.iter()
+		.flat_map(|wall| [angle_of(wall.p1), angle_of(wall.p2)])
+		.chain(
+			cache
+				.intersections
+				.iter()
+				.map(|intersection| angle_of(intersection.point)),
+		)
+		.collect::<Vec<_>>();
+	if let Some(vision_angle) =
+		VisionAngle::from_rotation_and_angle(call.rotation, call.angle, call.origin)
+	{
+		angles.push(vision_angle.start);
+		angles.push(vision_angle.end);
+	}
+	angles
+}
+
+/// The distance from the origin to the boundary of the polygon in the direction of the given angle.
+/// The polygon needs to be star shaped around the origin.
+pub fn polygon_distance(polygon: &[Point], origin: Point, angle: f64, length: f64) -> f64 {
+	let target = Point::new(
+		origin.x - angle.cos() * length,
+		origin.y - angle.sin() * length,
+	);
+	let mut distance: f64 = 0.0;
+	for (i, p1) in polygon.iter().enumerate() {
+		let p2 = polygon[(i + 1) % polygon.len()];
+		if let Some((t, u)) = segment_intersection_parameters(origin, target, *p1, p2) {
+			if (0.0..=1.0).contains(&u) {
+				distance = distance.max(t * length);
+			}
+		}
+	}
+	distance
+}
+
+// The sense of the wall for the source, including open doors, heights and roofs
+fn sense(call: &RaycastingCall, wall: &WallBase) -> WallSenseType {
+	if wall.door != DoorType::NONE && wall.ds == DoorState::OPEN {
+		return WallSenseType::NONE;
+	}
+	if call.height < wall.height.bottom || call.height > wall.height.top {
+		return WallSenseType::NONE;
+	}
+	match call.polygon_type {
+		PolygonType::SOUND => wall.sound,
+		PolygonType::MOVEMENT => wall.movement,
+		PolygonType::LIGHT => wall.sense,
+		// The walls of roofs that aren't occluded block sight completely
+		PolygonType::SIGHT => match wall.roof {
+			Some(roof) if !call.roofs[roof] => WallSenseType::NORMAL,
+			_ => wall.sense,
+		},
+	}
+}
+
+// Directional walls can be seen through from one of their sides
+fn is_see_through(call: &RaycastingCall, wall: &WallBase) -> bool {
+	let side = orient2d(wall.p1, wall.p2, call.origin);
+	match wall.dir {
+		WallDirection::BOTH => false,
+		WallDirection::LEFT => side > 0.0,
+		WallDirection::RIGHT => side < 0.0,
+	}
+}